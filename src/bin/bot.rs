@@ -0,0 +1,146 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    thread,
+    time::{Duration, Instant}
+};
+use bevy::prelude::*;
+use bevy_net_dev::{
+    dev::{
+        bot::{BotBehavior, BotConfig, BotPlugin, BotReport},
+        config::*,
+        game::GamePlugin
+    },
+    netstack::{
+        client::{setup_client, ClientConfig, ClientNetstackPlugin},
+        transport::NetstackTransport
+    }
+};
+
+const BOT_FRAME_DELTA: Duration = Duration::from_micros(16_667);
+// spreading connection requests over time
+const BOT_SPAWN_INTERVAL: Duration = Duration::from_millis(20);
+
+// --bots <count> --seconds <duration>
+fn parse_args() -> (usize, u64) {
+    let args = std::env::args().collect::<Vec<_>>();
+    let mut bots = 10;
+    let mut seconds = 60;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--bots" => {
+                bots = iter.next().and_then(|v| v.parse().ok()).expect("--bots needs count");
+            }
+            "--seconds" => {
+                seconds = iter.next().and_then(|v| v.parse().ok()).expect("--seconds needs duration");
+            }
+            _ => ()
+        }
+    }
+    (bots, seconds)
+}
+
+fn run_bot(client_id: u64, duration: Duration) -> BotReport {
+    let mut app = App::new();
+    app.insert_resource(ClientConfig{
+        client_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        server_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        server_port: DEV_SERVER_LISTEN_PORT,
+        timeout_seconds: DEV_CLIENT_TIME_OUT_SEC,
+        client_id,
+        protocol_id: get_dev_protocol_id(),
+        private_key: get_dev_private_key(),
+        user_data: get_dev_user_data(),
+        token_expire_seconds: DEV_TOKEN_EXPIRE_SEC,
+        transport: NetstackTransport::Udp,
+    })
+    .insert_resource(BotConfig{
+        behavior: BotBehavior::Random{
+            change_interval_seconds: 1.0,
+            fire_rate: 0.5
+        }
+    })
+    .add_plugins((
+        MinimalPlugins,
+        ClientNetstackPlugin
+    ))
+    .add_plugins((
+        GamePlugin,
+        BotPlugin
+    ))
+    .add_systems(Startup, setup_client);
+
+    // updated here instead of runner so that report can be taken at the end
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let frame_start = Instant::now();
+        app.update();
+        if let Some(rest) = BOT_FRAME_DELTA.checked_sub(frame_start.elapsed()) {
+            thread::sleep(rest);
+        }
+    }
+
+    app.world.resource::<BotReport>().clone()
+}
+
+fn main() {
+    let (bots, seconds) = parse_args();
+    let duration = Duration::from_secs(seconds);
+    let base_client_id = get_dev_client_id();
+    println!("running {bots} bots for {seconds} seconds");
+
+    let mut handles = Vec::with_capacity(bots);
+    for i in 0..bots {
+        let client_id = base_client_id + i as u64;
+        let bot_duration = duration.saturating_sub(BOT_SPAWN_INTERVAL * i as u32);
+        handles.push(thread::spawn(move || run_bot(client_id, bot_duration)));
+        thread::sleep(BOT_SPAWN_INTERVAL);
+    }
+
+    let reports = handles.into_iter()
+    .filter_map(|h| h.join().ok())
+    .collect::<Vec<_>>();
+
+    let connected = reports.iter().filter(|r| r.connected).collect::<Vec<_>>();
+    let disconnected = connected.iter().filter(|r| r.disconnected).collect::<Vec<_>>();
+    let crashed = bots - reports.len();
+    println!(
+        "connected: {}/{} disconnected: {} crashed: {}",
+        connected.len(), bots, disconnected.len(), crashed
+    );
+    for r in disconnected.iter() {
+        if let Some(reason) = r.disconnect_reason.as_ref() {
+            println!("disconnect reason: {reason}");
+        }
+    }
+    if connected.is_empty() {
+        return;
+    }
+
+    let count = connected.len() as f64;
+    let mean_connect = connected.iter()
+    .filter_map(|r| r.connect_seconds)
+    .sum::<f32>() as f64 / count;
+    let mean_rtt = connected.iter().map(|r| r.rtt_seconds).sum::<f64>() / count;
+    let max_rtt = connected.iter().map(|r| r.rtt_seconds).fold(0.0, f64::max);
+    let samples = connected.iter().map(|r| r.prediction.samples).sum::<u64>();
+    let corrections = connected.iter().map(|r| r.prediction.corrections).sum::<u64>();
+    let total_error = connected.iter().map(|r| r.prediction.total_error as f64).sum::<f64>();
+    let max_error = connected.iter().map(|r| r.prediction.max_error).fold(0.0, f32::max);
+    let actions = connected.iter().map(|r| r.actions_sent).sum::<u64>();
+    let errors = reports.iter().map(|r| r.errors).sum::<u64>();
+
+    println!("mean connect time: {:.3}s", mean_connect);
+    println!("rtt mean: {:.1}ms max: {:.1}ms", mean_rtt * 1000.0, max_rtt * 1000.0);
+    println!(
+        "prediction samples: {} corrections: {} mean error: {:.4} max error: {:.4}",
+        samples,
+        corrections,
+        if samples == 0 { 0.0 } else { total_error / samples as f64 },
+        max_error
+    );
+    println!("actions sent: {actions} netstack errors: {errors}");
+    if let Some(e) = reports.iter().find_map(|r| r.last_error.as_ref()) {
+        println!("last error: {e}");
+    }
+}
@@ -1,20 +1,33 @@
 use std::net::{IpAddr, Ipv4Addr};
-use bevy::prelude::*;
+use bevy::{app::AppExit, prelude::*};
 use bevy_net_dev::{
     dev::{
         config::*, 
         game::{GameIoPlugin, GamePlugin, KeyboardInputActionMap, MouseInputActionMap}, 
-        level::LevelPlugin
+        level::LevelPlugin,
+        match_state::MatchScreenPlugin,
+        score::ScoreboardScreenPlugin
     }, 
     netstack::{
         client::{setup_client, ClientConfig, ClientNetstackPlugin}, 
-        error::panic_on_net_error_system
+        conditioner::LinkConditionsHandle,
+        connection::ClientDisconnected,
+        error::log_net_error_system,
+        transport::NetstackTransport
     }
 };
 
 fn main() {
-    App::new()
-    .insert_resource(ClientConfig{
+    let args = std::env::args().collect::<Vec<_>>();
+    let link_conditions = parse_dev_link_conditions(&args)
+    .expect("invalid link conditions");
+
+    let mut app = App::new();
+    if let Some(c) = link_conditions {
+        app.insert_resource(LinkConditionsHandle::new(c));
+    }
+
+    app.insert_resource(ClientConfig{
         client_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
         server_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
         server_port: DEV_SERVER_LISTEN_PORT,
@@ -26,12 +39,14 @@ fn main() {
         // https://github.com/mas-bandwidth/netcode/blob/main/STANDARD.md
         user_data: get_dev_user_data(),
         token_expire_seconds: DEV_TOKEN_EXPIRE_SEC,
+        transport: NetstackTransport::Udp,
     })
     .insert_resource(KeyboardInputActionMap{
         movement_up: KeyCode::KeyW,
         movement_left: KeyCode::KeyA,
         movement_down: KeyCode::KeyS,
         movement_right: KeyCode::KeyD,
+        scoreboard: KeyCode::Tab
     })
     .insert_resource(MouseInputActionMap{
         fire: MouseButton::Left
@@ -43,10 +58,23 @@ fn main() {
     .add_plugins((
         GamePlugin,
         GameIoPlugin,
-        LevelPlugin
+        LevelPlugin,
+        MatchScreenPlugin,
+        ScoreboardScreenPlugin
     ))
     // connection to server is not triggered automatically
     .add_systems(Startup, setup_client)
-    .add_systems(Update, panic_on_net_error_system)
+    .add_systems(Update, (log_net_error_system, exit_on_disconnect_system))
     .run();
 }
+
+// kicked, rejected or lost connection, reconnecting is up to the player
+fn exit_on_disconnect_system(
+    mut disconnections: EventReader<ClientDisconnected>,
+    mut exit: EventWriter<AppExit>
+) {
+    for d in disconnections.read() {
+        info!("disconnected: {:?} rejected: {}", d.reason, d.rejected);
+        exit.send(AppExit);
+    }
+}
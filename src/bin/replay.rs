@@ -0,0 +1,77 @@
+use std::{path::PathBuf, process::ExitCode};
+use bevy_net_dev::{
+    dev::replay::Replayer,
+    netstack::recording::{replay_files, ReplayReader}
+};
+
+// replay <file or directory>...
+// a directory is expanded to its replay files in recorded order
+fn parse_args() -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for arg in std::env::args().skip(1) {
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            paths.extend(replay_files(&path)?);
+        } else {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+fn main() -> ExitCode {
+    let paths = match parse_args() {
+        Ok(p) if !p.is_empty() => p,
+        Ok(_) => {
+            eprintln!("usage: replay <file or directory>...");
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let header = match ReplayReader::open(&paths[0]) {
+        Ok(r) => r.header().clone(),
+        Err(e) => {
+            eprintln!("failed to open {:?}: {e}", paths[0]);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!(
+        "session started at: {} tick rate: {} replication interval: {} files: {}",
+        header.started_unix_ms,
+        header.simulation_tick.tick_rate,
+        header.simulation_tick.replication_interval,
+        paths.len()
+    );
+
+    let report = match Replayer::new(&header).run(&paths) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("replay failed: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "replayed frames: {} ticks: {:?} - {:?}",
+        report.frames, report.first_tick, report.last_tick
+    );
+    match report.divergence {
+        Some(d) => {
+            println!(
+                "diverged at step: {} tick: {} client: {:?} component: {}",
+                d.step, d.tick, d.client_id, d.component
+            );
+            println!("recorded: {}", d.recorded);
+            println!("replayed: {}", d.replayed);
+            ExitCode::FAILURE
+        }
+        None => {
+            println!("no divergence");
+            ExitCode::SUCCESS
+        }
+    }
+}
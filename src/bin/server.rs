@@ -4,27 +4,77 @@ use bevy_net_dev::{
     dev::{
         config::*, 
         game::GamePlugin, 
-        
+        match_state::MatchConfig
     },
     netstack::{ 
+        conditioner::LinkConditionsHandle,
         error::panic_on_net_error_system,
-        server::{ServerNetstackPlugin, ServerConfig}
+        lifecycle::ShutdownConfig,
+        priority::ReplicationBudget,
+        server::{ServerNetstackPlugin, ServerConfig},
+        transport::NetstackTransport,
+        visibility::InterestConfig
     }
 };
 
 fn main() {
-    App::new()
+    let args = std::env::args().collect::<Vec<_>>();
+    let link_conditions = parse_dev_link_conditions(&args)
+    .expect("invalid link conditions");
+    let recording = parse_dev_recording_config(&args)
+    .expect("invalid recording config");
+    let metrics = parse_dev_metrics_config(&args)
+    .expect("invalid metrics config");
+    let admin = parse_dev_admin_config(&args)
+    .expect("invalid admin config");
+
+    let mut app = App::new();
+    if let Some(c) = link_conditions {
+        app.insert_resource(LinkConditionsHandle::new(c));
+    }
+    if let Some(r) = recording {
+        app.insert_resource(r);
+    }
+    if let Some(m) = metrics {
+        app.insert_resource(m);
+    }
+    if let Some(a) = admin {
+        app.insert_resource(a);
+    }
+
+    app.insert_resource(ShutdownConfig{
+        handle_signals: true,
+        ..default()
+    })
+    .insert_resource(MatchConfig{
+        min_players: DEV_MATCH_MIN_PLAYERS,
+        max_players: DEV_MATCH_MAX_PLAYERS,
+        countdown_seconds: DEV_MATCH_COUNTDOWN_SEC,
+        match_seconds: DEV_MATCH_DURATION_SEC,
+        post_match_seconds: DEV_MATCH_POST_MATCH_SEC
+    })
+    .insert_resource(InterestConfig{
+        radius: DEV_INTEREST_RADIUS,
+        hysteresis: DEV_INTEREST_HYSTERESIS,
+        cell_size: DEV_INTEREST_CELL_SIZE
+    })
     .insert_resource(ServerConfig{
-        network_tick_rate: DEV_NETWORK_TICK_RATE,
+        simulation_tick_rate: DEV_SIMULATION_TICK_RATE,
+        replication_interval: DEV_REPLICATION_INTERVAL,
         listen_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
         listen_port: DEV_SERVER_LISTEN_PORT,
         protocol_id: get_dev_protocol_id(),
         private_key: get_dev_private_key(),
         max_clients: DEV_SERVER_MAX_CLIENTS,
+        transport: NetstackTransport::Udp,
+        replication_budget: ReplicationBudget{
+            bytes_per_client: DEV_REPLICATION_BYTES_PER_CLIENT,
+            ..default()
+        }
     })
     .add_plugins((
         MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(
-            Duration::from_secs_f32(DEV_SERVER_TICK_DELTA)
+            Duration::from_secs_f32(DEV_SIMULATION_TICK_DELTA)
         )),
         LogPlugin::default(),
         ServerNetstackPlugin
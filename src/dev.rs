@@ -1,3 +1,9 @@
 pub mod level;
 pub mod config;
-pub mod game;
\ No newline at end of file
+pub mod game;
+pub mod match_state;
+pub mod team;
+pub mod score;
+pub mod chat;
+pub mod bot;
+pub mod replay;
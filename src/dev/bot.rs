@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy_replicon_renet::renet::RenetClient;
+use bevy_replicon_snap::prelude::*;
+use rand::prelude::*;
+use crate::{
+    dev::game::{ActionEvent, PredictionStats},
+    netstack::{
+        connection::{ClientConnectionState, ClientDisconnected, DisconnectReason},
+        error::NetstackError,
+        time_sync::ServerClock
+    }
+};
+
+#[derive(Clone)]
+pub struct BotStep {
+    pub movement_vec: Vec2,
+    pub is_fire: bool,
+    pub duration_seconds: f32
+}
+
+#[derive(Clone)]
+pub enum BotBehavior {
+    // picks a new direction every interval
+    Random {
+        change_interval_seconds: f32,
+        // per second
+        fire_rate: f32
+    },
+    // repeats steps from the first one
+    Script(Vec<BotStep>)
+}
+
+#[derive(Resource, Clone)]
+pub struct BotConfig {
+    pub behavior: BotBehavior
+}
+
+#[derive(Resource, Default, Clone, Debug)]
+pub struct BotReport {
+    // connected at least once
+    pub connected: bool,
+    pub connect_seconds: Option<f32>,
+    // lost the connection after it was made
+    pub disconnected: bool,
+    pub disconnect_reason: Option<String>,
+    // latest while connected
+    pub rtt_seconds: f64,
+    pub prediction: PredictionStats,
+    pub actions_sent: u64,
+    pub errors: u64,
+    pub last_error: Option<String>
+}
+
+#[derive(Resource, Default)]
+struct BotState {
+    step: usize,
+    step_elapsed: f32,
+    fired_step: Option<usize>,
+    movement_vec: Vec2
+}
+
+// drives ActionEvent for headless clients instead of GameIoPlugin
+pub struct BotPlugin;
+
+impl Plugin for BotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BotReport>()
+        .init_resource::<BotState>()
+        .add_systems(Update, (
+            bot_action_system
+            .run_if(state_exists_and_equals(ClientConnectionState::Connected)),
+            bot_report_system.run_if(resource_exists::<RenetClient>)
+        ));
+    }
+}
+
+fn bot_action_system(
+    query: Query<(), With<OwnerControlling>>,
+    config: Res<BotConfig>,
+    mut state: ResMut<BotState>,
+    mut report: ResMut<BotReport>,
+    mut actions: EventWriter<ActionEvent>,
+    time: Res<Time>
+) {
+    // wait for own player
+    if query.is_empty() {
+        return;
+    }
+
+    let delta_time = time.delta_seconds();
+    state.step_elapsed += delta_time;
+
+    let mut rng = thread_rng();
+    let action = match &config.behavior {
+        BotBehavior::Random { change_interval_seconds, fire_rate } => {
+            if state.step_elapsed >= *change_interval_seconds || state.step == 0 {
+                state.step_elapsed = 0.0;
+                state.step += 1;
+                state.movement_vec = if rng.gen_bool(0.2) {
+                    Vec2::ZERO
+                } else {
+                    Vec2::new(
+                        rng.gen_range(-1..=1) as f32,
+                        rng.gen_range(-1..=1) as f32
+                    )
+                };
+            }
+            ActionEvent{
+                movement_vec: state.movement_vec,
+                is_fire: rng.gen::<f32>() < fire_rate * delta_time
+            }
+        }
+        BotBehavior::Script(steps) => {
+            if steps.is_empty() {
+                return;
+            }
+
+            let mut step = &steps[state.step % steps.len()];
+            if state.step_elapsed >= step.duration_seconds {
+                state.step_elapsed = 0.0;
+                state.step += 1;
+                step = &steps[state.step % steps.len()];
+            }
+            // fire once for each step
+            let is_fire = step.is_fire && state.fired_step != Some(state.step);
+            if is_fire {
+                state.fired_step = Some(state.step);
+            }
+            ActionEvent{
+                movement_vec: step.movement_vec,
+                is_fire
+            }
+        }
+    };
+
+    if action.has_action() {
+        actions.send(action);
+        report.actions_sent += 1;
+    }
+}
+
+fn bot_report_system(
+    renet_client: Res<RenetClient>,
+    server_clock: Res<ServerClock>,
+    prediction: Res<PredictionStats>,
+    mut errors: EventReader<NetstackError>,
+    mut disconnections: EventReader<ClientDisconnected>,
+    mut report: ResMut<BotReport>,
+    time: Res<Time<Real>>
+) {
+    if renet_client.is_connected() {
+        if !report.connected {
+            report.connected = true;
+            report.connect_seconds = Some(time.elapsed_seconds());
+        }
+        report.rtt_seconds = server_clock.rtt();
+    }
+    report.prediction = *prediction;
+
+    for d in disconnections.read() {
+        if !report.connected {
+            continue;
+        }
+        report.disconnected = true;
+        report.disconnect_reason = Some(match &d.reason {
+            DisconnectReason::Server(reason) => format!("server: {reason}"),
+            DisconnectReason::Transport(reason) => format!("transport: {reason}")
+        });
+    }
+
+    for e in errors.read() {
+        report.errors += 1;
+        report.last_error = Some(e.0.to_string());
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{
+    dev::{config::*, team::Team},
+    netstack::{
+        client_event::AcceptedFromClient,
+        components::{NetworkPlayer, ServerNetworkPlayerInfo},
+        connection::ClientConnectionState,
+        notification::{NotificationAppExt, Notify, Recipients},
+        rate_limit::{ClientEventRateLimitAppExt, RateLimit, RateLimitPolicy},
+        resources::PlayerEntityMap,
+        room::{is_visible_in_room, InRoom},
+        server::Server
+    }
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChatScope {
+    // everyone in the sender's room
+    All,
+    Team,
+    Whisper(ClientId)
+}
+
+// sent by client
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct ChatRequest {
+    pub scope: ChatScope,
+    pub text: String
+}
+
+// sent by server to every recipient, whispers are echoed to the sender
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub sender: ClientId,
+    pub sender_name: String,
+    pub scope: ChatScope,
+    pub text: String
+}
+
+pub enum ChatVerdict {
+    Allow,
+    Replace(String),
+    Reject
+}
+
+// profanity or spam hook, applied in registration order
+pub type ChatFilter = fn(&str) -> ChatVerdict;
+
+#[derive(Resource, Clone, Debug)]
+pub struct ChatConfig {
+    // in chars
+    pub max_length: usize,
+    // client keeps this many latest messages
+    pub history: usize
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self{
+            max_length: 200,
+            history: 50
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct ChatFilters(Vec<ChatFilter>);
+
+// received messages on client, oldest first
+#[derive(Resource, Default)]
+pub struct ChatLog(VecDeque<ChatMessage>);
+
+impl ChatLog {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.0.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+pub trait ChatAppExt {
+    fn add_chat_filter(&mut self, filter: ChatFilter) -> &mut Self;
+}
+
+impl ChatAppExt for App {
+    fn add_chat_filter(&mut self, filter: ChatFilter) -> &mut Self {
+        self.world.get_resource_or_insert_with(ChatFilters::default).0.push(filter);
+        self
+    }
+}
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChatConfig>()
+        .init_resource::<ChatFilters>()
+        .init_resource::<ChatLog>()
+        .add_client_event::<ChatRequest>(ChannelKind::Ordered)
+        .add_notification::<ChatMessage>(ChannelKind::Ordered)
+        .rate_limit_client_event::<ChatRequest>(RateLimit{
+            capacity: DEV_CHAT_RATE_LIMIT_CAPACITY,
+            refill_per_second: DEV_CHAT_RATE_LIMIT_PER_SEC,
+            policy: RateLimitPolicy::Drop
+        })
+        .add_systems(Update,
+            server_chat_system.run_if(resource_exists::<Server>)
+        )
+        .add_systems(Update,
+            client_chat_system
+            .run_if(state_exists_and_equals(ClientConnectionState::Connected))
+        );
+    }
+}
+
+// names come from client user data, same as texts they can not break chat lines
+fn display_name(info: &ServerNetworkPlayerInfo, client_id: ClientId) -> String {
+    let name = info.display_name().chars()
+    .filter(|c| !c.is_control())
+    .collect::<String>();
+    match name.trim() {
+        "" => format!("player {}", client_id.get()),
+        name => name.to_string()
+    }
+}
+
+fn validate(text: &str, config: &ChatConfig, filters: &ChatFilters) -> Option<String> {
+    let mut text = text.trim().to_string();
+    if text.is_empty() || text.chars().count() > config.max_length {
+        return None;
+    }
+    // clients can not break other clients' chat lines
+    if text.chars().any(|c| c.is_control()) {
+        return None;
+    }
+
+    for filter in filters.0.iter() {
+        match filter(&text) {
+            ChatVerdict::Allow => (),
+            ChatVerdict::Replace(replaced) => text = replaced,
+            ChatVerdict::Reject => return None
+        }
+    }
+    Some(text)
+}
+
+fn server_chat_system(
+    query: Query<(
+        &NetworkPlayer, &ServerNetworkPlayerInfo,
+        Option<&Team>, Option<&InRoom>
+    )>,
+    player_entities: Res<PlayerEntityMap>,
+    config: Res<ChatConfig>,
+    filters: Res<ChatFilters>,
+    mut requests: EventReader<AcceptedFromClient<ChatRequest>>,
+    mut messages: EventWriter<Notify<ChatMessage>>
+) {
+    for AcceptedFromClient { client_id, event } in requests.read() {
+        let Some((_, info, team, in_room)) = player_entities.get(client_id)
+        .and_then(|e| query.get(*e).ok()) else {
+            continue;
+        };
+        let Some(text) = validate(&event.text, &config, &filters) else {
+            warn!("client: {client_id:?} chat message is rejected");
+            continue;
+        };
+
+        let room = in_room.map(|r| r.0);
+        let recipients = match event.scope {
+            ChatScope::All => query.iter()
+            .filter(|(_, _, _, r)| is_visible_in_room(*r, room))
+            .map(|(p, ..)| p.client_id())
+            .collect::<Vec<_>>(),
+            ChatScope::Team => query.iter()
+            .filter(|(_, _, t, r)| is_visible_in_room(*r, room) && *t == team)
+            .map(|(p, ..)| p.client_id())
+            .collect(),
+            ChatScope::Whisper(target) => {
+                if player_entities.get(&target).is_none() {
+                    warn!("client: {client_id:?} whispered to unknown client: {target:?}");
+                    continue;
+                }
+                if target == *client_id {
+                    vec![target]
+                } else {
+                    vec![target, *client_id]
+                }
+            }
+        };
+
+        messages.send(Notify::new(Recipients::Many(recipients), ChatMessage{
+            sender: *client_id,
+            sender_name: display_name(info, *client_id),
+            scope: event.scope,
+            text
+        }));
+    }
+}
+
+fn client_chat_system(
+    mut messages: EventReader<ChatMessage>,
+    mut log: ResMut<ChatLog>,
+    config: Res<ChatConfig>
+) {
+    for message in messages.read() {
+        info!("[{:?}] {}: {}", message.scope, message.sender_name, message.text);
+        log.0.push_back(message.clone());
+        while log.0.len() > config.history {
+            log.0.pop_front();
+        }
+    }
+}
@@ -1,18 +1,58 @@
-use std::time::SystemTime;
+use std::{net::{Ipv4Addr, SocketAddr}, path::PathBuf, time::SystemTime};
 use bevy::utils::Uuid;
+use crate::netstack::{
+    admin::AdminConfig,
+    conditioner::{LinkConditionerConfig, LinkConditions},
+    metrics::MetricsConfig,
+    recording::RecordingConfig,
+    server::DISPLAY_NAME_USER_DATA_RANGE
+};
 
-pub const DEV_SERVER_TICK_RATE: f32 = 20.0;
-pub const DEV_SERVER_TICK_DELTA: f32 = 1.0 / DEV_SERVER_TICK_RATE;
-pub const DEV_NETWORK_TICK_RATE: u16 = 10;
-pub const DEV_NETWORK_TICK_DELTA: f32 = 1.0 / (DEV_NETWORK_TICK_RATE as f32); 
+pub const DEV_SIMULATION_TICK_RATE: u16 = 20;
+pub const DEV_SIMULATION_TICK_DELTA: f32 = 1.0 / (DEV_SIMULATION_TICK_RATE as f32);
+// network tick is 10Hz
+pub const DEV_REPLICATION_INTERVAL: u16 = 2;
 
 pub const DEV_SERVER_LISTEN_PORT: u16 = 5000;
-pub const DEV_SERVER_MAX_CLIENTS: usize = 10;
+// enough for soak testing with bots
+pub const DEV_SERVER_MAX_CLIENTS: usize = 128;
 
 pub const DEV_CLIENT_TIME_OUT_SEC: i32 = 15;
 pub const DEV_TOKEN_EXPIRE_SEC: u64 = 300;
 
 pub const DEV_MAX_BUFFER_SIZE: usize = 100;
+pub const DEV_INPUT_REDUNDANCY: usize = 8;
+
+// movement is sent every frame while keys are pressed,
+// so the rate follows frame rate and extra inputs are only dropped
+pub const DEV_MOVEMENT_RATE_LIMIT_CAPACITY: u32 = 240;
+pub const DEV_MOVEMENT_RATE_LIMIT_PER_SEC: f32 = 180.0;
+pub const DEV_FIRE_RATE_LIMIT_CAPACITY: u32 = 5;
+pub const DEV_FIRE_RATE_LIMIT_PER_SEC: f32 = 5.0;
+pub const DEV_FIRE_RANGE: f32 = 15.0;
+// distance from the line of fire a target is still hit at
+pub const DEV_FIRE_HIT_RADIUS: f32 = 1.0;
+pub const DEV_CHAT_RATE_LIMIT_CAPACITY: u32 = 5;
+pub const DEV_CHAT_RATE_LIMIT_PER_SEC: f32 = 1.0;
+
+pub const DEV_RECORDING_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+pub const DEV_RECORDING_MAX_FILES: usize = 16;
+
+pub const DEV_METRICS_UPDATE_INTERVAL_SEC: f32 = 1.0;
+
+// players move 10 units per second
+pub const DEV_INTEREST_RADIUS: f32 = 60.0;
+pub const DEV_INTEREST_HYSTERESIS: f32 = 5.0;
+pub const DEV_INTEREST_CELL_SIZE: f32 = 30.0;
+
+// fits in a single packet
+pub const DEV_REPLICATION_BYTES_PER_CLIENT: usize = 1200;
+
+pub const DEV_MATCH_MIN_PLAYERS: usize = 2;
+pub const DEV_MATCH_MAX_PLAYERS: usize = 16;
+pub const DEV_MATCH_COUNTDOWN_SEC: f32 = 5.0;
+pub const DEV_MATCH_DURATION_SEC: f32 = 180.0;
+pub const DEV_MATCH_POST_MATCH_SEC: f32 = 10.0;
 
 pub fn get_dev_protocol_id() -> u64 {
     if cfg!(debug_assertions) {
@@ -44,14 +84,128 @@ pub fn get_dev_client_id() -> u64 {
     }
 }
 
+pub fn get_dev_admin_password() -> String {
+    if cfg!(debug_assertions) {
+        std::env::var("DEV_ADMIN_PASSWORD").unwrap_or_else(|_| "dev".to_string())
+    } else {
+        panic!("do not use dev admin password");
+    }
+}
+
 pub fn get_dev_user_data() -> [u8; 256] {
     if cfg!(debug_assertions) {
         // this will be session id generated by backend service
         let mut user_data = [0u8; 256];
         user_data[0..16].copy_from_slice(Uuid::new_v4().as_bytes());
+        let name = std::env::var("DEV_DISPLAY_NAME").unwrap_or_else(|_| "dev".to_string());
+        // cut at a char boundary so that the name stays valid utf-8
+        let mut len = name.len().min(DISPLAY_NAME_USER_DATA_RANGE.len());
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        user_data[DISPLAY_NAME_USER_DATA_RANGE.start..][..len].copy_from_slice(&name.as_bytes()[..len]);
         user_data
     } else {
         panic!("do not use dev user data")
     }
 }
 
+// --link <spec> for both directions, --link-up <spec> and --link-down <spec> for each
+// spec is like "latency=100,jitter=20,loss=0.05"
+pub fn parse_dev_link_conditions(args: &[String]) -> anyhow::Result<Option<LinkConditions>> {
+    let mut conditions = None::<LinkConditions>;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let apply: fn(&mut LinkConditions, LinkConditionerConfig) = match arg.as_str() {
+            "--link" => |c, l| {
+                c.upstream = l;
+                c.downstream = l;
+            },
+            "--link-up" => |c, l| c.upstream = l,
+            "--link-down" => |c, l| c.downstream = l,
+            _ => continue
+        };
+
+        let spec = iter.next()
+        .ok_or_else(|| anyhow::anyhow!("{arg} needs link conditions"))?;
+        apply(conditions.get_or_insert_with(LinkConditions::default), spec.parse()?);
+    }
+    Ok(conditions)
+}
+
+// --record <directory> starts session recording on startup
+pub fn parse_dev_recording_config(args: &[String]) -> anyhow::Result<Option<RecordingConfig>> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg != "--record" {
+            continue;
+        }
+
+        let directory = iter.next()
+        .ok_or_else(|| anyhow::anyhow!("{arg} needs directory"))?;
+        return Ok(Some(RecordingConfig{
+            directory: PathBuf::from(directory),
+            max_file_bytes: DEV_RECORDING_MAX_FILE_BYTES,
+            max_files: DEV_RECORDING_MAX_FILES,
+            start_on_startup: true
+        }));
+    }
+    Ok(None)
+}
+
+// --metrics <port> serves metrics on localhost
+pub fn parse_dev_metrics_config(args: &[String]) -> anyhow::Result<Option<MetricsConfig>> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg != "--metrics" {
+            continue;
+        }
+
+        let port = iter.next()
+        .ok_or_else(|| anyhow::anyhow!("{arg} needs port"))?
+        .parse::<u16>()?;
+        return Ok(Some(MetricsConfig{
+            listen_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+            update_interval_seconds: DEV_METRICS_UPDATE_INTERVAL_SEC
+        }));
+    }
+    Ok(None)
+}
+
+// --admin <port> for tcp console on localhost, --admin-stdin for stdin,
+// --admin-stdin-no-auth for stdin without the auth line
+pub fn parse_dev_admin_config(args: &[String]) -> anyhow::Result<Option<AdminConfig>> {
+    let mut config = None::<AdminConfig>;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--admin" => {
+                let port = iter.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} needs port"))?
+                .parse::<u16>()?;
+                config.get_or_insert_with(default_dev_admin_config).listen_addr = Some(
+                    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+                );
+            }
+            "--admin-stdin" => {
+                config.get_or_insert_with(default_dev_admin_config).stdin = true;
+            }
+            "--admin-stdin-no-auth" => {
+                let config = config.get_or_insert_with(default_dev_admin_config);
+                config.stdin = true;
+                config.stdin_without_auth = true;
+            }
+            _ => ()
+        }
+    }
+    Ok(config)
+}
+
+fn default_dev_admin_config() -> AdminConfig {
+    AdminConfig{
+        listen_addr: None,
+        password: get_dev_admin_password(),
+        stdin: false,
+        stdin_without_auth: false
+    }
+}
@@ -1,24 +1,45 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
 use bevy_replicon::{
     client::ServerEntityTicks, 
     core::replicon_tick::RepliconTick, 
     prelude::*
 };
+use bevy_replicon_renet::renet::transport::NetcodeServerTransport;
 use bevy_replicon_snap::prelude::*;
 use serde::{Serialize, Deserialize};
 use rand::prelude::*;
 use anyhow::anyhow;
 use crate::{
-    dev::config::{DEV_MAX_BUFFER_SIZE, DEV_NETWORK_TICK_DELTA}, 
+    dev::{
+        chat::ChatPlugin,
+        config::*,
+        match_state::{is_match_running, MatchState, MatchStatePlugin},
+        score::ScorePlugin,
+        team::{assign_team, Team, TeamConfig, TeamPlugin}
+    },
     netstack::{
+        admin::AdminCommandAppExt,
         client::Client, 
+        client_event::AcceptedFromClient,
+        connection::ClientConnectionState,
         components::{
             MinimalNetworkTransform, MinimalNetworkTransformSnapshots, 
             NetClient, NetworkPlayer, NetworkTranslation2D, NetworkYaw, Owner
         }, 
         error::NetstackError, 
         events::{NetworkFireEvent, NetworkMovement2DEvent},
-        server::Server
+        input::{NetworkInputAck, RedundantInputAppExt, ServerInputQueue, UnackedInputs},
+        loopback::LoopbackServerTransport,
+        metrics::MetricsAppExt,
+        priority::ReplicationPriorityAppExt,
+        rate_limit::{ClientEventRateLimitAppExt, RateLimit, RateLimitPolicy},
+        recording::RecordingAppExt,
+        resources::PlayerEntityMap,
+        room::{is_visible_in_room, InRoom, Rooms},
+        sequence::InputSequencer,
+        tick::SimulationTickConfig,
+        time_sync::{EstimatedServerTick, InterpolationTick, ServerClock},
+        server::{client_user_data, Server}
     }
 };
 
@@ -26,34 +47,70 @@ pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app
+        app.add_plugins((MatchStatePlugin, TeamPlugin, ScorePlugin, ChatPlugin))
         .insert_resource(PlayerMovementParams{
             base_speed: 10.0,
             prediction_error_threashold: 1.0
         })
+        .init_resource::<PredictionStats>()
+        .add_event::<ActionEvent>()
+        .add_event::<LagCompensatedFireEvent>()
+        .add_event::<HitEvent>()
         .use_client_event_snapshots::<NetworkMovement2DEvent>(
             ChannelKind::Unreliable, 
             DEV_MAX_BUFFER_SIZE
         )
+        .use_redundant_inputs::<NetworkMovement2DEvent>(
+            DEV_INPUT_REDUNDANCY, 
+            DEV_MAX_BUFFER_SIZE
+        )
         .use_component_snapshot::<NetworkTranslation2D>()
         .use_component_snapshot::<NetworkYaw>()
         .add_client_event::<NetworkFireEvent>(ChannelKind::Ordered)
+        .rate_limit_client_event::<NetworkMovement2DEvent>(RateLimit{
+            capacity: DEV_MOVEMENT_RATE_LIMIT_CAPACITY,
+            refill_per_second: DEV_MOVEMENT_RATE_LIMIT_PER_SEC,
+            policy: RateLimitPolicy::Drop
+        })
+        .rate_limit_client_event::<NetworkFireEvent>(RateLimit{
+            capacity: DEV_FIRE_RATE_LIMIT_CAPACITY,
+            refill_per_second: DEV_FIRE_RATE_LIMIT_PER_SEC,
+            policy: RateLimitPolicy::Drop
+        })
         .replicate::<PlayerPresentation>()
         .replicate::<NetworkTranslation2D>()
         .replicate::<NetworkYaw>()
+        .record_component::<PlayerPresentation>()
+        .record_component::<NetworkTranslation2D>()
+        .record_component::<NetworkYaw>()
+        .record_client_event::<NetworkMovement2DEvent>()
+        .record_client_event::<NetworkFireEvent>()
+        .export_snapshot_buffer::<NetworkTranslation2D>()
+        .export_snapshot_buffer::<NetworkYaw>()
+        .export_input_queue::<NetworkMovement2DEvent>()
+        .prioritize_replication::<PlayerPresentation>()
+        .prioritize_replication::<NetworkTranslation2D>()
+        .prioritize_replication::<NetworkYaw>()
+        .add_admin_command(
+            "movement", 
+            "movement [speed|threshold] [value]", 
+            movement_command
+        )
         .add_systems(FixedUpdate, 
-            client_move_2d_system.run_if(resource_exists::<Client>)
+            client_move_2d_system
+            .run_if(state_exists_and_equals(ClientConnectionState::Connected))
         )
         .add_systems(Update, (
             client_on_player_spawned,
-            apply_network_transform_system
-        ).run_if(resource_exists::<Client>))
+            apply_network_transform_system,
+            handle_action_event_system
+        ).run_if(state_exists_and_equals(ClientConnectionState::Connected)))
         .add_systems(FixedUpdate, 
             server_move_2d_system.run_if(resource_exists::<Server>)
         )
         .add_systems(Update, (
             server_on_player_spawned,
-            server_on_fire
+            (server_on_fire, server_process_hits).chain()
         ).run_if(resource_exists::<Server>));
     }
 }
@@ -62,12 +119,9 @@ pub struct GameIoPlugin;
 
 impl Plugin for GameIoPlugin {
     fn build(&self, app: &mut App) {
-        app
-        .add_event::<ActionEvent>()
-        .add_systems(Update, (
-            handle_input_system,
-            handle_action_event_system
-        ).chain());
+        app.add_systems(Update, 
+            handle_input_system.before(handle_action_event_system)
+        );
     }
 }
 
@@ -77,12 +131,64 @@ pub struct PlayerMovementParams {
     pub prediction_error_threashold: f32
 }
 
+fn movement_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let mut params = world.resource_mut::<PlayerMovementParams>();
+    match (args.first(), args.get(1)) {
+        (Some(&"speed"), Some(v)) => params.base_speed = v.parse()?,
+        (Some(&"threshold"), Some(v)) => params.prediction_error_threashold = v.parse()?,
+        (None, None) => (),
+        _ => return Err(anyhow!("expected speed or threshold with value"))
+    }
+    // clients predict with their own params, they are corrected by server
+    Ok(format!(
+        "speed: {} threshold: {}",
+        params.base_speed, params.prediction_error_threashold
+    ))
+}
+
+// client side prediction accuracy of owned player
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct PredictionStats {
+    pub samples: u64,
+    pub corrections: u64,
+    pub total_error: f32,
+    pub max_error: f32
+}
+
+impl PredictionStats {
+    #[inline]
+    pub fn mean_error(&self) -> f32 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.total_error / self.samples as f32
+        }
+    }
+
+    #[inline]
+    fn record(&mut self, error: f32, corrected: bool) {
+        self.samples += 1;
+        self.total_error += error;
+        self.max_error = self.max_error.max(error);
+        if corrected {
+            self.corrections += 1;
+        }
+    }
+}
+
 #[derive(Component, Serialize, Deserialize)]
 pub struct PlayerPresentation {
     pub color: Color
 }
 
 impl PlayerPresentation {
+    #[inline]
+    pub fn from_team(team: Team) -> Self {
+        Self{
+            color: team.color()
+        }
+    }
+
     #[inline]
     pub fn from_rand_color() -> Self {
         Self{
@@ -100,7 +206,8 @@ pub struct KeyboardInputActionMap {
     pub movement_up: KeyCode,
     pub movement_left: KeyCode,
     pub movement_down: KeyCode,
-    pub movement_right: KeyCode
+    pub movement_right: KeyCode,
+    pub scoreboard: KeyCode
 }
 
 #[derive(Resource)]
@@ -108,6 +215,27 @@ pub struct MouseInputActionMap {
     pub fire: MouseButton
 }
 
+// server side transform of a player at the time shooter saw it
+#[derive(Event, Clone, Debug)]
+pub struct LagCompensatedFireEvent {
+    pub shooter: ClientId,
+    // shooter at the same ticks as the target
+    pub shooter_translation: Vec2,
+    pub shooter_yaw: f32,
+    pub target: Entity,
+    pub translation: Vec2,
+    pub translation_tick: u32,
+    pub yaw: f32,
+    pub yaw_tick: u32
+}
+
+// fire which hit a target, friendly fire is already filtered
+#[derive(Event, Clone, Debug)]
+pub struct HitEvent {
+    pub shooter: ClientId,
+    pub target: Entity
+}
+
 #[derive(Event, Default)]
 pub struct ActionEvent {
     pub movement_vec: Vec2,
@@ -164,20 +292,39 @@ fn handle_action_event_system(
     )>,
     mut actions: EventReader<ActionEvent>,
     mut movements: EventWriter<NetworkMovement2DEvent>,
-    mut fires: EventWriter<NetworkFireEvent>
+    mut fires: EventWriter<NetworkFireEvent>,
+    mut sequencer: ResMut<InputSequencer>,
+    match_state: Query<&MatchState>,
+    server_clock: Res<ServerClock>,
+    estimated_tick: Res<EstimatedServerTick>,
+    interpolation_tick: Res<InterpolationTick>
 ) {
+    // server would ignore them
+    if !is_match_running(&match_state) {
+        actions.clear();
+        return;
+    }
+
     if let Ok((_, net_t2d_buff, net_yaw_buff)) = query.get_single() {
-        for (a, event_id) in actions.read_with_id() {
+        for a in actions.read() {
             if a.has_movement() {
                 movements.send(NetworkMovement2DEvent{
                     axis: a.movement_vec,
-                    index: event_id.id
+                    sequence: sequencer.next(),
+                    tick: estimated_tick.0
                 });
             }
             if a.is_fire {
+                // fire at the state others are shown,
+                // latest snapshot is the best guess until clock is synced
+                let (network_translation_tick, network_yaw_tick) = if server_clock.is_synced() {
+                    (interpolation_tick.0, interpolation_tick.0)
+                } else {
+                    (net_t2d_buff.latest_snapshot_tick(), net_yaw_buff.latest_snapshot_tick())
+                };
                 fires.send(NetworkFireEvent{
-                    network_translation_tick: net_t2d_buff.latest_snapshot_tick(),
-                    network_yaw_tick: net_yaw_buff.latest_snapshot_tick()
+                    network_translation_tick,
+                    network_yaw_tick
                 });
             }
         }
@@ -187,40 +334,69 @@ fn handle_action_event_system(
 fn server_on_player_spawned(
     mut commands: Commands,
     query: Query<(Entity, &NetworkPlayer), Added<NetworkPlayer>>,
+    teams: Query<&Team>,
+    players: Query<&NetworkTranslation2D, With<NetworkPlayer>>,
+    team_config: Res<TeamConfig>,
+    netcode_server: Option<Res<NetcodeServerTransport>>,
+    loopback_server: Option<Res<LoopbackServerTransport>>,
     replicon_tick: Res<RepliconTick>
 ) {
+    let mut members = HashMap::<Team, usize>::default();
+    for team in teams.iter() {
+        *members.entry(*team).or_default() += 1;
+    }
+    // slots of players who left are free again
+    let mut occupied = players.iter().map(|t| t.0).collect::<Vec<_>>();
+
     for (e, p) in query.iter() {
         let tick = replicon_tick.get();
-        info!("player: {:?} spawned at tick: {}", p.client_id(), tick);
+        let requested = client_user_data(
+            netcode_server.as_deref(),
+            loopback_server.as_deref(),
+            &p.client_id()
+        )
+        .and_then(|u| Team::from_user_data(&u));
+        let team = assign_team(requested, &members);
+        *members.entry(team).or_default() += 1;
+        let translation = NetworkTranslation2D(team_config.free_spawn_point(team, &occupied));
+        occupied.push(translation.0);
+        info!("player: {:?} spawned at tick: {} in team: {:?}", p.client_id(), tick, team);
         
         let mut translation_snaps = ComponentSnapshotBuffer::with_capacity(DEV_MAX_BUFFER_SIZE);
         // this is for safety pushing older-than-any value
         // other client's latest network tick can be much older than this new client
         // (when they have not moved, synced for a while)
         // this value is catched as latest old value for events from those clients
-        translation_snaps.insert(default(), 0);
-        translation_snaps.insert(default(), tick);
+        translation_snaps.insert(translation.clone(), 0);
+        translation_snaps.insert(translation.clone(), tick);
         let mut rotation_snaps = ComponentSnapshotBuffer::with_capacity(DEV_MAX_BUFFER_SIZE); 
         rotation_snaps.insert(default(), 0);
         rotation_snaps.insert(default(), tick);
 
         commands.entity(e)
         .insert((
-            MinimalNetworkTransform::default(),
+            MinimalNetworkTransform{
+                translation,
+                ..default()
+            },
             MinimalNetworkTransformSnapshots {
                 translation_snaps,
                 rotation_snaps
             },
             Owner::new(p.client_id().get()),
-            PlayerPresentation::from_rand_color()
+            NetworkInputAck::<NetworkMovement2DEvent>::default(),
+            PlayerPresentation::from_team(team),
+            team,
+            team.notification_group()
         ));
     }
 }
 
 fn client_on_player_spawned(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    // not available for headless clients
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
     query: Query<(
         Entity, 
         &NetworkPlayer, &PlayerPresentation, 
@@ -231,6 +407,7 @@ fn client_on_player_spawned(
     client: Res<Client>,
     server_ticks: Res<ServerEntityTicks>
 ) {
+    let mut render_assets = meshes.zip(materials);
     for (e, p, presentation, net_t2d, net_yaw) in query.iter() {
         let server_tick = match server_ticks.get(&e) {
             Some(tick) => tick.get(),
@@ -256,18 +433,27 @@ fn client_on_player_spawned(
         rotation_snaps.insert(net_yaw.clone(), 0);
         rotation_snaps.insert(net_yaw.clone(), server_tick);
 
+        let transform = Transform{
+            translation: net_t2d.to_3d(),
+            rotation: net_yaw.to_3d(),
+            scale: Vec3::ONE
+        };
+        match render_assets.as_mut() {
+            Some((meshes, materials)) => {
+                commands.entity(e).insert(PbrBundle{
+                    mesh: meshes.add(Mesh::from(Capsule3d::default())),
+                    material: materials.add(presentation.color),
+                    transform,
+                    ..default()
+                });
+            }
+            None => {
+                commands.entity(e).insert(TransformBundle::from_transform(transform));
+            }
+        }
+
         commands.entity(e)
         .insert((
-            PbrBundle{
-                mesh: meshes.add(Mesh::from(Capsule3d::default())),
-                material: materials.add(presentation.color),
-                transform: Transform{
-                    translation: net_t2d.to_3d(),
-                    rotation: net_yaw.to_3d(),
-                    scale: Vec3::ONE
-                },
-                ..default()
-            },
             MinimalNetworkTransformSnapshots {
                 translation_snaps,
                 rotation_snaps
@@ -283,29 +469,46 @@ fn client_on_player_spawned(
 }
 
 pub fn server_move_2d_system(
-    mut query: Query<(&NetworkPlayer, &mut NetworkTranslation2D)>,
-    mut movement_snaps: ResMut<EventSnapshotClientMap<NetworkMovement2DEvent>>,
+    mut query: Query<(
+        &NetworkPlayer, 
+        &mut NetworkTranslation2D, 
+        &mut NetworkYaw,
+        &mut NetworkInputAck<NetworkMovement2DEvent>
+    )>,
+    mut movements: ResMut<ServerInputQueue<NetworkMovement2DEvent>>,
+    match_state: Query<&MatchState>,
     movement_params: Res<PlayerMovementParams>,
     fixed_time: Res<Time<Fixed>>,
     replicon_tick: Res<RepliconTick>,
 ) {
-    for (net_p, mut net_t2d) in query.iter_mut() {
+    // inputs out of a match are acked but not simulated
+    let running = is_match_running(&match_state);
+    for (net_p, mut net_t2d, mut net_yaw, mut ack) in query.iter_mut() {
         let client_id = net_p.client_id();
-        
-        movement_snaps.sort_with_id(&client_id);
-        let frontier = movement_snaps.frontier(&client_id);
-        if frontier.len() == 0 {
+        if movements.len(&client_id) == 0 {
             continue;
         }
         
         let tick = replicon_tick.get();
         let delta_time = fixed_time.delta_seconds();
         
+        // queued in sequence order by the input merge
         let mut t2d = net_t2d.clone();
-        for movement in frontier {
-            move_2d(&mut t2d, movement.event(), &movement_params, delta_time);
+        let mut yaw = net_yaw.0;
+        for event in movements.drain(&client_id) {
+            if running {
+                move_2d(&mut t2d, &event, &movement_params, delta_time);
+                // players aim where they walk
+                if let Some(dir) = movement_direction(&event) {
+                    yaw = direction_to_yaw(dir);
+                }
+            }
+            ack.set(event.sequence);
         }
         net_t2d.0 = t2d.0;
+        if net_yaw.0 != yaw {
+            net_yaw.0 = yaw;
+        }
 
         debug!(
             "client: {:?} server translation: {} on tick: {} delta time: {}", 
@@ -324,16 +527,20 @@ pub fn client_move_2d_system(
         With<ClientPrediction>, 
         With<OwnerControlling>
     )>,
+    unacked: Res<UnackedInputs<NetworkMovement2DEvent>>,
+    mut prediction_stats: ResMut<PredictionStats>,
     movement_params: Res<PlayerMovementParams>,
     server_ticks: Res<ServerEntityTicks>,
     fixed_time: Res<Time<Fixed>>,
     mut errors: EventWriter<NetstackError>
 ) {
     for (e, mut t, net_t2d, mut movement_buff) in query.iter_mut() {
-        let frontier = movement_buff.frontier();
-        if frontier.len() == 0 {
+        // buffer index does not survive sequence wrap around
+        let mut frontier = movement_buff.frontier().into_iter().collect::<Vec<_>>();
+        if frontier.is_empty() {
             continue;
         }
+        frontier.sort_by(|a, b| a.event().sequence.cmp_wrapping(&b.event().sequence));
 
         let server_tick = match server_ticks.get(&e) {
             Some(tick) => tick.get(),
@@ -347,34 +554,49 @@ pub fn client_move_2d_system(
         let delta_time = fixed_time.delta_seconds();
         
         let mut client_t2d = NetworkTranslation2D::from_3d(t.translation);
-        let mut server_t2d = net_t2d.clone();
-
         for movement in frontier {
-            let event = movement.event();
-            move_2d(&mut client_t2d, event, &movement_params, delta_time);
-            move_2d(&mut server_t2d, event, &movement_params, delta_time);
+            move_2d(&mut client_t2d, movement.event(), &movement_params, delta_time);
+        }
+
+        // server translation is the result of inputs until acked index
+        // replay only the rest on top of it
+        let mut server_t2d = net_t2d.clone();
+        for movement in unacked.iter() {
+            move_2d(&mut server_t2d, movement, &movement_params, delta_time);
         }
         debug!("predicted translation: {} on tick {}", client_t2d.0, server_tick);
-        debug!("corrected translation: {} on tick {}", server_t2d.0, server_tick);
+        debug!(
+            "corrected translation: {} on tick {} acked input: {:?}", 
+            server_t2d.0, server_tick, unacked.acked_sequence()
+        );
 
         let prediction_error = server_t2d.0.distance(client_t2d.0);
-        if prediction_error > movement_params.prediction_error_threashold {
+        let corrected = prediction_error > movement_params.prediction_error_threashold;
+        if corrected {
             t.translation = server_t2d.to_3d();
             warn!("prediction error(length): {prediction_error} overwritten by server");
         } else {
             t.translation = client_t2d.to_3d();
         }
+        prediction_stats.record(prediction_error, corrected);
     }
 }
 
+// network space direction of the input, axis y is up on screen
+fn movement_direction(movement: &NetworkMovement2DEvent) -> Option<Vec2> {
+    let dir = movement.axis.try_normalize()?;
+    Some(Vec2::new(dir.x, -dir.y))
+}
+
 fn move_2d(
     translation: &mut NetworkTranslation2D,
     movement: &NetworkMovement2DEvent,
     params: &PlayerMovementParams,
     delta_time: f32
 ) {
-    let mut dir = movement.axis.normalize();
-    dir.y *= -1.0;
+    let Some(dir) = movement_direction(movement) else {
+        return;
+    };
     translation.0 += dir * (params.base_speed * delta_time); 
 }
 
@@ -387,37 +609,85 @@ fn apply_network_transform_system(
         With<InterpolatedReplication>, With<ClientPrediction>, 
         Without<OwnerControlling>
     )>,
+    simulation_tick: Option<Res<SimulationTickConfig>>,
     time: Res<Time>
 ) {
+    let Some(simulation_tick) = simulation_tick else {
+        return;
+    };
+    
+    let network_tick_delta = simulation_tick.network_tick_delta();
     for (mut t, net_t, net_t_buff, net_y, net_y_buff) in query.iter_mut() {
         let delta_time = time.delta_seconds();
         let mut interpolated_t = net_t.clone();
-        interpolate(&mut interpolated_t, net_t_buff, delta_time, DEV_NETWORK_TICK_DELTA);
+        interpolate(&mut interpolated_t, net_t_buff, delta_time, network_tick_delta);
         let mut interpolated_y = net_y.clone();
-        interpolate(&mut interpolated_y, net_y_buff, delta_time, DEV_NETWORK_TICK_DELTA);
+        interpolate(&mut interpolated_y, net_y_buff, delta_time, network_tick_delta);
 
         t.translation = interpolated_t.to_3d();
         t.rotation = interpolated_y.to_3d();
     }
 }
 
+// latest snapshot at or before the tick
+pub fn snapshot_index_at<C: Component + Interpolate + Clone>(
+    buffer: &ComponentSnapshotBuffer<C>, 
+    tick: u32
+) -> Option<usize> {
+    buffer.iter().rposition(|s| s.tick() <= tick)
+}
+
 fn server_on_fire(
     query: Query<(
+        Entity,
         &ComponentSnapshotBuffer<NetworkTranslation2D>,
-        &ComponentSnapshotBuffer<NetworkYaw>
+        &ComponentSnapshotBuffer<NetworkYaw>,
+        Option<&InRoom>
 )   >,
-    mut fires: EventReader<FromClient<NetworkFireEvent>>
+    mut fires: EventReader<AcceptedFromClient<NetworkFireEvent>>,
+    mut lag_compensated: EventWriter<LagCompensatedFireEvent>,
+    match_state: Query<&MatchState>,
+    player_entities: Res<PlayerEntityMap>,
+    rooms: Res<Rooms>
 ) {
-    for FromClient { client_id, event } in fires.read() {
+    if !is_match_running(&match_state) {
+        fires.clear();
+        return;
+    }
+
+    for AcceptedFromClient { client_id, event } in fires.read() {
         info!(
             "player: {client_id:?} fired at it's translation tick: {} yaw tick: {}",
             event.network_translation_tick, 
             event.network_yaw_tick
         );
 
-        for (net_t2d_buff, net_yaw_buff) in query.iter() {
-            let net_t2d_idx = match net_t2d_buff.iter()
-            .rposition(|s| s.tick() <= event.network_translation_tick) {
+        let Some((shooter_translation, shooter_yaw)) = player_entities.get(client_id)
+        .and_then(|e| query.get(*e).ok())
+        .and_then(|(_, net_t2d_buff, net_yaw_buff, _)| {
+            let net_t2d_snap = net_t2d_buff.get(
+                snapshot_index_at(net_t2d_buff, event.network_translation_tick)?
+            )?;
+            let net_yaw_snap = net_yaw_buff.get(
+                snapshot_index_at(net_yaw_buff, event.network_yaw_tick)?
+            )?;
+            Some((net_t2d_snap.component().0, net_yaw_snap.component().0))
+        }) else {
+            warn!("player: {client_id:?} has no state at the fire tick, ignoring...");
+            continue;
+        };
+
+        let shooter_room = rooms.client_room(client_id);
+        for (e, net_t2d_buff, net_yaw_buff, in_room) in query.iter() {
+            // other rooms are other games
+            if !is_visible_in_room(in_room, shooter_room) {
+                continue;
+            }
+
+            let net_t2d_idx = match snapshot_index_at(
+                net_t2d_buff, 
+                event.network_translation_tick
+            ) {
                 Some(idx) => idx, 
                 None => {
                     if cfg!(debug_assertions) {
@@ -434,8 +704,10 @@ fn server_on_fire(
             let net_t2d_snap = net_t2d_buff.get(net_t2d_idx)
             .unwrap(); // must has some here 
 
-            let net_yaw_idx = match net_yaw_buff.iter()
-            .rposition(|s| s.tick() <= event.network_yaw_tick) {
+            let net_yaw_idx = match snapshot_index_at(
+                net_yaw_buff, 
+                event.network_yaw_tick
+            ) {
                 Some(idx) => idx,
                 None => {
                     if cfg!(debug_assertions) {
@@ -457,6 +729,67 @@ fn server_on_fire(
                 net_t2d_snap.component().0, net_t2d_snap.tick(),
                 net_yaw_snap.component().0, net_yaw_snap.tick(),
             );
+            lag_compensated.send(LagCompensatedFireEvent{
+                shooter: *client_id,
+                shooter_translation,
+                shooter_yaw,
+                target: e,
+                translation: net_t2d_snap.component().0,
+                translation_tick: net_t2d_snap.tick(),
+                yaw: net_yaw_snap.component().0,
+                yaw_tick: net_yaw_snap.tick()
+            });
+        }
+    }
+}
+
+// yaw is in degrees and 0 faces -z as bevy forward
+pub fn yaw_to_direction(yaw: f32) -> Vec2 {
+    let (sin, cos) = yaw.to_radians().sin_cos();
+    Vec2::new(-sin, -cos)
+}
+
+pub fn direction_to_yaw(direction: Vec2) -> f32 {
+    (-direction.x).atan2(-direction.y).to_degrees()
+}
+
+// fire is a ray from the shooter along its yaw,
+// both sides are at the state shooter saw them
+fn server_process_hits(
+    query: Query<Option<&Team>>,
+    player_entities: Res<PlayerEntityMap>,
+    team_config: Res<TeamConfig>,
+    mut fires: EventReader<LagCompensatedFireEvent>,
+    mut hits: EventWriter<HitEvent>
+) {
+    for fire in fires.read() {
+        let Some(shooter) = player_entities.get(&fire.shooter) else {
+            continue;
+        };
+        if *shooter == fire.target {
+            continue;
+        }
+
+        let to_target = fire.translation - fire.shooter_translation;
+        let aim = yaw_to_direction(fire.shooter_yaw);
+        let along = to_target.dot(aim);
+        if along < 0.0 || along > DEV_FIRE_RANGE {
+            continue;
+        }
+        if (to_target - aim * along).length() > DEV_FIRE_HIT_RADIUS {
+            continue;
+        }
+
+        let shooter_team = query.get(*shooter).ok().flatten();
+        let target_team = query.get(fire.target).ok().flatten();
+        if !team_config.can_hit(shooter_team, target_team) {
+            debug!("player: {:?} friendly fire is ignored", fire.shooter);
+            continue;
         }
+        info!("player: {:?} hit: {:?}", fire.shooter, fire.target);
+        hits.send(HitEvent{
+            shooter: fire.shooter,
+            target: fire.target
+        });
     }
 }
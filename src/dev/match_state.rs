@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+use serde::{Deserialize, Serialize};
+use anyhow::bail;
+use crate::netstack::{
+    admin::{tick_rate_command, AdminCommandAppExt},
+    components::NetworkPlayer,
+    connection::ClientConnectionState,
+    lifecycle::{DisconnectClient, DisconnectNotice},
+    notification::{NotificationAppExt, Notify, Recipients},
+    recording::RecordingAppExt,
+    server::{handle_server_event_system, Server},
+    tick::SimulationTickConfig,
+    time_sync::EstimatedServerTick
+};
+
+pub const MATCH_FULL_REASON: &str = "match is full";
+
+// server runs matches only when this is inserted,
+// otherwise players roam freely
+#[derive(Resource, Clone, Debug)]
+pub struct MatchConfig {
+    pub min_players: usize,
+    // 0 for unlimited
+    pub max_players: usize,
+    pub countdown_seconds: f32,
+    pub match_seconds: f32,
+    pub post_match_seconds: f32
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchPhase {
+    Waiting,
+    Countdown,
+    InProgress,
+    PostMatch
+}
+
+// replicated on a single entity spawned by server
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct MatchState {
+    pub phase: MatchPhase,
+    // replicon tick the phase ends at, waiting ends by players
+    pub ends_at: Option<u32>,
+    pub players: u32,
+    pub min_players: u32
+}
+
+impl MatchState {
+    // remaining seconds of the phase at the tick
+    pub fn remaining_seconds(&self, tick: u32, config: &SimulationTickConfig) -> Option<f32> {
+        self.ends_at.map(|end| end.saturating_sub(tick) as f32 * config.network_tick_delta())
+    }
+}
+
+// sent on both sides when the phase changes,
+// on client from the replicated state
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MatchPhaseChanged {
+    pub from: Option<MatchPhase>,
+    pub to: MatchPhase
+}
+
+// sent to every client on phase change
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct MatchNotice {
+    pub phase: MatchPhase,
+    // length of the phase, waiting ends by players
+    pub seconds: Option<f32>
+}
+
+// free roam when there is no match state
+pub fn is_match_running(query: &Query<&MatchState>) -> bool {
+    match query.get_single() {
+        Ok(state) => state.phase == MatchPhase::InProgress,
+        Err(_) => true
+    }
+}
+
+pub struct MatchStatePlugin;
+
+impl Plugin for MatchStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MatchPhaseChanged>()
+        .add_notification::<MatchNotice>(ChannelKind::Ordered)
+        .replicate::<MatchState>()
+        // replays need the phase to know which inputs were simulated
+        .record_component::<MatchState>()
+        .add_admin_command("match", "match [start|end]", match_command)
+        // replaces the netstack one, deadlines of a running match are in ticks
+        .add_admin_command("tickrate", "tickrate <hz> [replication interval]", match_tick_rate_command)
+        .add_systems(Update, (
+            spawn_match_state_system,
+            // players of rejected clients are not spawned
+            reject_over_capacity_system.before(handle_server_event_system),
+            match_transition_system,
+            notify_match_phase_system
+        ).chain().run_if(
+            resource_exists::<Server>.and_then(resource_exists::<MatchConfig>)
+        ))
+        .add_systems(Update, (
+            client_match_state_system,
+            receive_match_notice_system
+        ).run_if(state_exists_and_equals(ClientConnectionState::Connected)));
+    }
+}
+
+fn seconds_to_ticks(seconds: f32, config: &SimulationTickConfig) -> u32 {
+    (seconds * config.network_tick_rate()).ceil() as u32
+}
+
+fn spawn_match_state_system(
+    mut commands: Commands,
+    query: Query<(), With<MatchState>>,
+    config: Res<MatchConfig>,
+    mut changes: EventWriter<MatchPhaseChanged>
+) {
+    if !query.is_empty() {
+        return;
+    }
+
+    commands.spawn((
+        MatchState{
+            phase: MatchPhase::Waiting,
+            ends_at: None,
+            players: 0,
+            min_players: config.min_players as u32
+        },
+        Replication
+    ));
+    changes.send(MatchPhaseChanged{ from: None, to: MatchPhase::Waiting });
+    info!("match: waiting for {} players", config.min_players);
+}
+
+fn reject_over_capacity_system(
+    config: Res<MatchConfig>,
+    mut server_events: EventReader<ServerEvent>,
+    players: Query<(), With<NetworkPlayer>>,
+    mut disconnects: EventWriter<DisconnectClient>
+) {
+    if config.max_players == 0 {
+        server_events.clear();
+        return;
+    }
+
+    // clients connecting in this update are not spawned yet
+    let mut accepted = players.iter().count();
+    for e in server_events.read() {
+        if let ServerEvent::ClientConnected { client_id } = e {
+            if accepted < config.max_players {
+                accepted += 1;
+                continue;
+            }
+            disconnects.send(DisconnectClient{
+                client_id: *client_id,
+                notice: DisconnectNotice{ reason: MATCH_FULL_REASON.to_string(), rejected: true }
+            });
+            info!("client: {client_id:?} is rejected, {MATCH_FULL_REASON}");
+        }
+    }
+}
+
+fn match_transition_system(
+    mut query: Query<&mut MatchState>,
+    players: Query<(), With<NetworkPlayer>>,
+    config: Res<MatchConfig>,
+    simulation_tick: Res<SimulationTickConfig>,
+    replicon_tick: Res<RepliconTick>,
+    mut changes: EventWriter<MatchPhaseChanged>
+) {
+    let Ok(mut state) = query.get_single_mut() else {
+        return;
+    };
+
+    let tick = replicon_tick.get();
+    let player_count = players.iter().count();
+    let enough = player_count >= config.min_players;
+    let ended = state.ends_at.is_some_and(|end| tick >= end);
+    let next = match state.phase {
+        MatchPhase::Waiting if enough => Some((MatchPhase::Countdown, config.countdown_seconds)),
+        MatchPhase::Countdown if !enough => Some((MatchPhase::Waiting, 0.0)),
+        MatchPhase::Countdown if ended => Some((MatchPhase::InProgress, config.match_seconds)),
+        MatchPhase::InProgress if ended || player_count == 0 => {
+            Some((MatchPhase::PostMatch, config.post_match_seconds))
+        }
+        MatchPhase::PostMatch if ended => Some((MatchPhase::Waiting, 0.0)),
+        _ => None
+    };
+
+    if state.players != player_count as u32 {
+        state.players = player_count as u32;
+    }
+    let Some((phase, seconds)) = next else {
+        return;
+    };
+
+    let from = state.phase;
+    state.phase = phase;
+    state.ends_at = match phase {
+        MatchPhase::Waiting => None,
+        _ => Some(tick + seconds_to_ticks(seconds, &simulation_tick))
+    };
+    changes.send(MatchPhaseChanged{ from: Some(from), to: phase });
+    info!("match: {from:?} -> {phase:?} at tick: {tick} with {player_count} players");
+}
+
+fn notify_match_phase_system(
+    config: Res<MatchConfig>,
+    mut changes: EventReader<MatchPhaseChanged>,
+    mut notices: EventWriter<Notify<MatchNotice>>
+) {
+    for change in changes.read() {
+        let seconds = match change.to {
+            MatchPhase::Waiting => None,
+            MatchPhase::Countdown => Some(config.countdown_seconds),
+            MatchPhase::InProgress => Some(config.match_seconds),
+            MatchPhase::PostMatch => Some(config.post_match_seconds)
+        };
+        notices.send(Notify::new(
+            Recipients::All,
+            MatchNotice{ phase: change.to, seconds }
+        ));
+    }
+}
+
+fn receive_match_notice_system(mut notices: EventReader<MatchNotice>) {
+    for notice in notices.read() {
+        match (notice.phase, notice.seconds) {
+            (MatchPhase::Countdown, Some(s)) => info!("match starting in {s} seconds"),
+            (MatchPhase::InProgress, Some(s)) => info!("match started, {s} seconds to go"),
+            (MatchPhase::PostMatch, _) => info!("match is over"),
+            _ => info!("waiting for players")
+        }
+    }
+}
+
+fn client_match_state_system(
+    query: Query<&MatchState, Changed<MatchState>>,
+    mut last_phase: Local<Option<MatchPhase>>,
+    mut changes: EventWriter<MatchPhaseChanged>
+) {
+    let Ok(state) = query.get_single() else {
+        return;
+    };
+    if *last_phase == Some(state.phase) {
+        return;
+    }
+
+    changes.send(MatchPhaseChanged{ from: *last_phase, to: state.phase });
+    info!("match: {:?} -> {:?}", *last_phase, state.phase);
+    *last_phase = Some(state.phase);
+}
+
+fn match_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let tick = world.resource::<RepliconTick>().get();
+    let mut query = world.query::<&mut MatchState>();
+    let Ok(mut state) = query.get_single_mut(world) else {
+        bail!("match is not running");
+    };
+
+    match args {
+        [] => (),
+        // skips the rest of the current phase
+        ["start"] if state.phase == MatchPhase::Countdown => state.ends_at = Some(tick),
+        ["end"] if state.phase == MatchPhase::InProgress => state.ends_at = Some(tick),
+        ["start"] | ["end"] => bail!("can not {} in {:?}", args[0], state.phase),
+        _ => bail!("expected start or end")
+    }
+    Ok(format!(
+        "phase: {:?} players: {}/{} ends at: {:?} tick: {tick}",
+        state.phase, state.players, state.min_players, state.ends_at
+    ))
+}
+
+fn match_tick_rate_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    if !args.is_empty() {
+        let mut query = world.query::<&MatchState>();
+        if let Ok(state) = query.get_single(world) {
+            if state.phase != MatchPhase::Waiting {
+                bail!("tick rate can change only while waiting for players: {:?}", state.phase);
+            }
+        }
+    }
+    tick_rate_command(world, args)
+}
+
+// client screen showing match phase
+pub struct MatchScreenPlugin;
+
+impl Plugin for MatchScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_match_screen)
+        .add_systems(Update, update_match_screen_system);
+    }
+}
+
+#[derive(Component)]
+struct MatchScreenText;
+
+fn setup_match_screen(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle{
+            font_size: 32.0,
+            color: Color::WHITE,
+            ..default()
+        })
+        .with_style(Style{
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        MatchScreenText
+    ));
+}
+
+fn update_match_screen_system(
+    mut texts: Query<&mut Text, With<MatchScreenText>>,
+    query: Query<&MatchState>,
+    simulation_tick: Option<Res<SimulationTickConfig>>,
+    estimated_tick: Option<Res<EstimatedServerTick>>
+) {
+    let Ok(mut text) = texts.get_single_mut() else {
+        return;
+    };
+    let Ok(state) = query.get_single() else {
+        text.sections[0].value.clear();
+        return;
+    };
+
+    let remaining = simulation_tick.zip(estimated_tick)
+    .and_then(|(config, tick)| state.remaining_seconds(tick.0, &config))
+    .unwrap_or_default()
+    .ceil();
+    text.sections[0].value = match state.phase {
+        MatchPhase::Waiting => format!(
+            "waiting for players {}/{}", state.players, state.min_players
+        ),
+        MatchPhase::Countdown => format!("match starts in {remaining}"),
+        MatchPhase::InProgress => format!("{remaining}"),
+        MatchPhase::PostMatch => "match is over".to_string()
+    };
+}
@@ -0,0 +1,359 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    time::Duration
+};
+use bevy::{prelude::*, time::TimeUpdateStrategy, utils::{HashMap, Uuid}};
+use bevy_replicon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use anyhow::{anyhow, bail};
+use crate::{
+    dev::{config::*, game::GamePlugin, match_state::MatchState},
+    netstack::{
+        client_event::ClientEventQueue,
+        components::{NetworkPlayer, NetworkTranslation2D, NetworkYaw, ServerNetworkPlayerInfo},
+        events::{NetworkFireEvent, NetworkMovement2DEvent},
+        loopback::LoopbackNetwork,
+        recording::{RecordedComponent, RecordedEvent, ReplayFrame, ReplayHeader, ReplayReader},
+        resources::PlayerEntityMap,
+        server::{ServerConfig, ServerNetstackPlugin, ServerNetstackSet},
+        transport::NetstackTransport
+    }
+};
+
+// components compared tick by tick, others such as random colors are not deterministic
+pub trait ReplayComponent: Component + Serialize + DeserializeOwned {
+    fn describe(&self) -> String;
+}
+
+impl ReplayComponent for NetworkTranslation2D {
+    fn describe(&self) -> String {
+        format!("{}", self.0)
+    }
+}
+
+impl ReplayComponent for NetworkYaw {
+    fn describe(&self) -> String {
+        format!("{}", self.0)
+    }
+}
+
+struct VerifiedComponent {
+    name: &'static str,
+    read: fn(&World, Entity) -> Option<anyhow::Result<Vec<u8>>>,
+    apply: fn(&mut World, Entity, &RecordedComponent) -> anyhow::Result<()>,
+    describe: fn(&[u8]) -> String
+}
+
+impl VerifiedComponent {
+    fn new<C: ReplayComponent>() -> Self {
+        Self{
+            name: std::any::type_name::<C>(),
+            read: |world, e| {
+                world.get::<C>(e).map(|c| Ok(bincode::serialize(c)?))
+            },
+            apply: |world, e, recorded| {
+                let c = recorded.decode::<C>()?;
+                world.entity_mut(e).insert(c);
+                Ok(())
+            },
+            describe: |data| match bincode::deserialize::<C>(data) {
+                Ok(c) => c.describe(),
+                Err(e) => format!("undecodable: {e}")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplayDivergence {
+    pub step: u64,
+    pub tick: u32,
+    pub client_id: Option<u64>,
+    pub component: &'static str,
+    pub recorded: String,
+    pub replayed: String
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub frames: u64,
+    pub first_tick: Option<u32>,
+    pub last_tick: Option<u32>,
+    pub divergence: Option<ReplayDivergence>
+}
+
+// recorded inputs waiting for the next update,
+// they were recorded after the filters so that they skip them
+#[derive(Resource, Default)]
+struct ReplayInputs {
+    movements: Vec<(ClientId, NetworkMovement2DEvent)>,
+    fires: Vec<(ClientId, NetworkFireEvent)>
+}
+
+// re-simulates recorded sessions with the game server systems
+// and compares the state with the recording after every simulation step
+pub struct Replayer {
+    app: App,
+    step_delta: Duration,
+    header: Option<ReplayHeader>,
+    verified: HashMap<u16, VerifiedComponent>,
+    // recorded entity to replayed entity
+    entities: HashMap<u64, Entity>,
+    client_ids: HashMap<u64, u64>,
+    match_kind: Option<u16>,
+    // replayed match state, game systems skip inputs out of a running match
+    match_state: Option<Entity>,
+    // latest recorded state of verified components
+    expected: HashMap<(u64, u16), Vec<u8>>,
+    report: ReplayReport
+}
+
+impl Replayer {
+    pub fn new(header: &ReplayHeader) -> Self {
+        let simulation_tick = header.simulation_tick;
+        let step_delta = Duration::from_secs_f64(1.0 / simulation_tick.tick_rate as f64);
+
+        let mut app = App::new();
+        app.insert_resource(ServerConfig{
+            simulation_tick_rate: simulation_tick.tick_rate,
+            replication_interval: simulation_tick.replication_interval,
+            listen_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            listen_port: 0,
+            protocol_id: 0,
+            private_key: [0; 32],
+            max_clients: DEV_SERVER_MAX_CLIENTS,
+            // nobody connects, players are spawned from the recording
+            transport: NetstackTransport::Loopback(LoopbackNetwork::default()),
+            // every change is replayed as recorded
+            replication_budget: default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+        .init_resource::<ReplayInputs>()
+        .add_plugins((
+            MinimalPlugins,
+            ServerNetstackPlugin
+        ))
+        .add_plugins(GamePlugin)
+        .add_systems(PreUpdate,
+            accept_replay_inputs_system
+            .after(ServerNetstackSet::ClientEventFilter)
+            .before(ServerNetstackSet::ClientEventAccept)
+        );
+        // startup without simulation
+        app.update();
+
+        Self{
+            app,
+            step_delta,
+            header: None,
+            verified: default(),
+            entities: default(),
+            client_ids: default(),
+            match_kind: None,
+            match_state: None,
+            expected: default(),
+            report: default()
+        }
+    }
+
+    // rotated parts of one session are given in order
+    pub fn run(mut self, paths: &[PathBuf]) -> anyhow::Result<ReplayReport> {
+        for path in paths {
+            let mut reader = ReplayReader::open(path)?;
+            self.use_header(reader.header().clone())?;
+            info!("replaying: {path:?}");
+
+            while let Some(frame) = reader.next_frame()? {
+                self.step(frame)?;
+                if self.report.divergence.is_some() {
+                    return Ok(self.report);
+                }
+            }
+        }
+        Ok(self.report)
+    }
+
+    fn use_header(&mut self, header: ReplayHeader) -> anyhow::Result<()> {
+        if let Some(previous) = self.header.as_ref() {
+            if previous.started_unix_ms != header.started_unix_ms {
+                bail!("replay files are from different sessions");
+            }
+        }
+
+        self.verified.clear();
+        self.verify::<NetworkTranslation2D>(&header);
+        self.verify::<NetworkYaw>(&header);
+        self.match_kind = header.component_kind::<MatchState>();
+        if self.match_kind.is_none() {
+            warn!("{} is not recorded", std::any::type_name::<MatchState>());
+        }
+        self.header = Some(header);
+        Ok(())
+    }
+
+    fn verify<C: ReplayComponent>(&mut self, header: &ReplayHeader) {
+        match header.component_kind::<C>() {
+            Some(kind) => {
+                self.verified.insert(kind, VerifiedComponent::new::<C>());
+            }
+            None => warn!("{} is not recorded", std::any::type_name::<C>())
+        }
+    }
+
+    fn step(&mut self, frame: ReplayFrame) -> anyhow::Result<()> {
+        self.despawn_players(&frame);
+        let joined = self.spawn_players(&frame);
+        self.queue_inputs(&frame.events, &joined)?;
+        self.restore_match_state(&frame)?;
+        self.app.insert_resource(TimeUpdateStrategy::ManualDuration(self.step_delta));
+        self.app.update();
+        // recorded state of joined players is already the result of this step
+        self.restore_players(&frame, &joined)?;
+
+        for c in frame.components.iter() {
+            if self.verified.contains_key(&c.kind) {
+                self.expected.insert((c.entity, c.kind), c.data.clone());
+            }
+        }
+
+        self.report.frames += 1;
+        self.report.first_tick.get_or_insert(frame.tick);
+        self.report.last_tick = Some(frame.tick);
+        self.report.divergence = self.compare(&frame);
+        Ok(())
+    }
+
+    fn despawn_players(&mut self, frame: &ReplayFrame) {
+        for recorded in frame.despawned.iter() {
+            let Some(e) = self.entities.remove(recorded) else {
+                continue;
+            };
+            if let Some(client_id) = self.client_ids.remove(recorded) {
+                self.app.world.resource_mut::<PlayerEntityMap>()
+                .remove(&ClientId::new(client_id));
+            }
+            self.app.world.despawn(e);
+            self.expected.retain(|(entity, _), _| entity != recorded);
+        }
+    }
+
+    // returns players recorded with their state,
+    // those were already in game when the recording started
+    fn spawn_players(&mut self, frame: &ReplayFrame) -> Vec<u64> {
+        let mut joined = vec![];
+        for p in frame.spawned.iter() {
+            if self.entities.contains_key(&p.entity) {
+                continue;
+            }
+
+            let client_id = ClientId::new(p.client_id);
+            let e = self.app.world.spawn((
+                ServerNetworkPlayerInfo::new(Uuid::from_bytes(p.uuid)),
+                NetworkPlayer::new(client_id)
+            )).id();
+            if let Err(e) = self.app.world.resource_mut::<PlayerEntityMap>().try_insert(client_id, e) {
+                warn!("replayed player is not mapped: {e}");
+            }
+            self.entities.insert(p.entity, e);
+            self.client_ids.insert(p.entity, p.client_id);
+
+            if frame.components.iter().any(|c| c.entity == p.entity) {
+                joined.push(p.entity);
+            }
+        }
+        joined
+    }
+
+    // game systems have set joined players up in the step,
+    // others keep their simulated state
+    fn restore_players(&mut self, frame: &ReplayFrame, joined: &[u64]) -> anyhow::Result<()> {
+        for c in frame.components.iter().filter(|c| joined.contains(&c.entity)) {
+            let (Some(verified), Some(e)) = (self.verified.get(&c.kind), self.entities.get(&c.entity)) else {
+                continue;
+            };
+            (verified.apply)(&mut self.app.world, *e, c)?;
+        }
+        Ok(())
+    }
+
+    // phase changes outside of simulation steps,
+    // so that the recorded one is the phase this step ran in
+    fn restore_match_state(&mut self, frame: &ReplayFrame) -> anyhow::Result<()> {
+        let Some(recorded) = frame.components.iter()
+        .rfind(|c| Some(c.kind) == self.match_kind) else {
+            return Ok(());
+        };
+
+        let state = recorded.decode::<MatchState>()?;
+        match self.match_state {
+            Some(e) => {
+                self.app.world.entity_mut(e).insert(state);
+            }
+            None => {
+                self.match_state = Some(self.app.world.spawn(state).id());
+            }
+        }
+        Ok(())
+    }
+
+    // inputs of joined players are already in their restored state
+    fn queue_inputs(&mut self, events: &[RecordedEvent], joined: &[u64]) -> anyhow::Result<()> {
+        let header = self.header.as_ref()
+        .ok_or_else(|| anyhow!("replay header should be read"))?;
+        let movement_kind = header.event_kind::<NetworkMovement2DEvent>();
+        let fire_kind = header.event_kind::<NetworkFireEvent>();
+        let restored = joined.iter()
+        .filter_map(|e| self.client_ids.get(e).copied())
+        .collect::<Vec<_>>();
+
+        let mut inputs = self.app.world.resource_mut::<ReplayInputs>();
+        for e in events.iter().filter(|e| !restored.contains(&e.client_id)) {
+            let client_id = ClientId::new(e.client_id);
+            if Some(e.kind) == movement_kind {
+                inputs.movements.push((client_id, e.decode()?));
+            } else if Some(e.kind) == fire_kind {
+                inputs.fires.push((client_id, e.decode()?));
+            }
+        }
+        Ok(())
+    }
+
+    fn compare(&self, frame: &ReplayFrame) -> Option<ReplayDivergence> {
+        for ((recorded, kind), data) in self.expected.iter() {
+            let (Some(verified), Some(e)) = (self.verified.get(kind), self.entities.get(recorded)) else {
+                continue;
+            };
+
+            let replayed = match (verified.read)(&self.app.world, *e) {
+                Some(Ok(replayed)) if replayed == *data => continue,
+                Some(Ok(replayed)) => (verified.describe)(&replayed),
+                Some(Err(e)) => format!("unserializable: {e}"),
+                None => "missing".to_string()
+            };
+            return Some(ReplayDivergence{
+                step: frame.step,
+                tick: frame.tick,
+                client_id: self.client_ids.get(recorded).copied(),
+                component: verified.name,
+                recorded: (verified.describe)(data),
+                replayed
+            });
+        }
+        None
+    }
+}
+
+// merge, rate limits and other filters already ran when they were recorded
+fn accept_replay_inputs_system(
+    mut inputs: ResMut<ReplayInputs>,
+    mut movements: ResMut<ClientEventQueue<NetworkMovement2DEvent>>,
+    mut fires: ResMut<ClientEventQueue<NetworkFireEvent>>
+) {
+    for (client_id, event) in inputs.movements.drain(..) {
+        movements.push(client_id, event);
+    }
+    for (client_id, event) in inputs.fires.drain(..) {
+        fires.push(client_id, event);
+    }
+}
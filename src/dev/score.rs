@@ -0,0 +1,457 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+use bevy_replicon_snap::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{
+    dev::{
+        game::{HitEvent, KeyboardInputActionMap},
+        match_state::{MatchPhase, MatchPhaseChanged},
+        team::{assign_team, Team, TeamConfig}
+    },
+    netstack::{
+        client::Client,
+        connection::ClientConnectionState,
+        components::{NetworkPlayer, NetworkTranslation2D},
+        notification::{NotificationAppExt, NotificationGroup, Notify, Recipients},
+        recording::RecordingAppExt,
+        resources::PlayerEntityMap,
+        room::{InRoom, Rooms},
+        server::Server,
+        stats::NetworkStats
+    }
+};
+
+#[derive(Resource, Clone, Debug)]
+pub struct ScoreConfig {
+    pub max_health: u32,
+    pub hit_damage: u32,
+    // ping on scoreboard is refreshed this often, not on every sample
+    pub ping_update_seconds: f32
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        Self{
+            max_health: 100,
+            hit_damage: 25,
+            ping_update_seconds: 1.0
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Health(pub u32);
+
+// players who hit since the last death, server only
+#[derive(Component, Default)]
+pub struct Attackers(Vec<ClientId>);
+
+#[derive(Event, Clone, Debug)]
+pub struct DeathEvent {
+    pub victim: ClientId,
+    pub killer: ClientId,
+    pub assists: Vec<ClientId>
+}
+
+// sent to the victim's team and the killer
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct DeathNotice {
+    pub victim: ClientId,
+    pub killer: ClientId
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ScoreEntry {
+    pub client_id: ClientId,
+    pub team: Option<Team>,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub ping_ms: u32
+}
+
+impl ScoreEntry {
+    fn new(client_id: ClientId, team: Option<Team>) -> Self {
+        Self{
+            client_id,
+            team,
+            kills: 0,
+            deaths: 0,
+            assists: 0,
+            ping_ms: 0
+        }
+    }
+}
+
+// replicated on an entity per room spawned by server, lists players of that room only,
+// not on players so that interest management does not hide entries
+#[derive(Component, Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Scoreboard {
+    entries: Vec<ScoreEntry>
+}
+
+impl Scoreboard {
+    #[inline]
+    pub fn get(&self, client_id: &ClientId) -> Option<&ScoreEntry> {
+        self.entries.iter().find(|e| e.client_id == *client_id)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &ScoreEntry> {
+        self.entries.iter()
+    }
+
+    #[inline]
+    fn get_mut(&mut self, client_id: &ClientId) -> Option<&mut ScoreEntry> {
+        self.entries.iter_mut().find(|e| e.client_id == *client_id)
+    }
+
+    // most kills first, then least deaths
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| b.kills.cmp(&a.kills)
+            .then(a.deaths.cmp(&b.deaths))
+            .then(a.client_id.get().cmp(&b.client_id.get()))
+        );
+    }
+}
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScoreConfig>()
+        .add_event::<DeathEvent>()
+        .add_notification::<DeathNotice>(ChannelKind::Ordered)
+        .replicate::<Health>()
+        .replicate::<Scoreboard>()
+        .record_component::<Health>()
+        .add_systems(Update, (
+            spawn_scoreboards_system,
+            insert_health_system,
+            apply_hits_system,
+            scoreboard_members_system,
+            scoreboard_death_system,
+            notify_death_system,
+            scoreboard_ping_system,
+            reset_scores_system
+        ).chain().run_if(resource_exists::<Server>))
+        .add_systems(Update,
+            receive_death_notice_system
+            .run_if(state_exists_and_equals(ClientConnectionState::Connected))
+        );
+    }
+}
+
+// destroyed rooms despawn their scoreboard with the rest of their entities
+fn spawn_scoreboards_system(
+    mut commands: Commands,
+    query: Query<&InRoom, With<Scoreboard>>,
+    rooms: Res<Rooms>
+) {
+    let spawned = query.iter().map(|r| r.0).collect::<HashSet<_>>();
+    for (id, _) in rooms.iter().filter(|(id, _)| !spawned.contains(*id)) {
+        commands.spawn((Scoreboard::default(), InRoom(*id), Replication));
+    }
+}
+
+fn insert_health_system(
+    mut commands: Commands,
+    query: Query<Entity, (With<NetworkPlayer>, Without<Health>)>,
+    config: Res<ScoreConfig>
+) {
+    for e in query.iter() {
+        commands.entity(e).insert((Health(config.max_health), Attackers::default()));
+    }
+}
+
+// killed players respawn at a free spawn point of their team right away,
+// players without a team on the side of the smaller one
+fn apply_hits_system(
+    mut query: Query<(
+        Entity, &NetworkPlayer, &mut Health, &mut Attackers,
+        &mut NetworkTranslation2D, &mut ComponentSnapshotBuffer<NetworkTranslation2D>,
+        Option<&Team>
+    )>,
+    config: Res<ScoreConfig>,
+    team_config: Res<TeamConfig>,
+    replicon_tick: Res<RepliconTick>,
+    mut hits: EventReader<HitEvent>,
+    mut deaths: EventWriter<DeathEvent>
+) {
+    let mut killed = vec![];
+    for hit in hits.read() {
+        let Ok((e, p, mut health, mut attackers, ..)) = query.get_mut(hit.target) else {
+            continue;
+        };
+        // killed already by an earlier hit of this update
+        if health.0 == 0 {
+            continue;
+        }
+
+        health.0 = health.0.saturating_sub(config.hit_damage);
+        if !attackers.0.contains(&hit.shooter) {
+            attackers.0.push(hit.shooter);
+        }
+        if health.0 > 0 {
+            continue;
+        }
+
+        let assists = attackers.0.drain(..)
+        .filter(|id| *id != hit.shooter)
+        .collect::<Vec<_>>();
+        info!("player: {:?} is killed by: {:?}", p.client_id(), hit.shooter);
+        deaths.send(DeathEvent{
+            victim: p.client_id(),
+            killer: hit.shooter,
+            assists
+        });
+        killed.push(e);
+    }
+    if killed.is_empty() {
+        return;
+    }
+
+    let mut members = HashMap::<Team, usize>::default();
+    for (.., team) in query.iter() {
+        if let Some(team) = team {
+            *members.entry(*team).or_default() += 1;
+        }
+    }
+    let tick = replicon_tick.get();
+    for e in killed {
+        let occupied = query.iter()
+        .filter(|(other, ..)| *other != e)
+        .map(|(.., t, _, _)| t.0)
+        .collect::<Vec<_>>();
+        let Ok((_, p, mut health, _, mut translation, mut snapshots, team)) = query.get_mut(e) else {
+            continue;
+        };
+
+        let side = team.copied().unwrap_or_else(|| assign_team(None, &members));
+        health.0 = config.max_health;
+        translation.0 = team_config.free_spawn_point(side, &occupied);
+        // fire looked up from now on does not find the corpse
+        snapshots.insert(translation.clone(), tick);
+        info!("player: {:?} respawned at: {} tick: {}", p.client_id(), translation.0, tick);
+    }
+}
+
+// players moving to another room start over on its scoreboard
+fn scoreboard_members_system(
+    mut query: Query<(&InRoom, &mut Scoreboard)>,
+    players: Query<(&NetworkPlayer, &InRoom, Option<&Team>)>
+) {
+    for (room, mut scoreboard) in query.iter_mut() {
+        let teams = players.iter()
+        .filter(|(_, r, _)| *r == room)
+        .map(|(p, _, t)| (p.client_id(), t.copied()))
+        .collect::<HashMap<_, _>>();
+        let changed = scoreboard.entries.len() != teams.len()
+        || scoreboard.entries.iter().any(|e| teams.get(&e.client_id) != Some(&e.team));
+        if !changed {
+            continue;
+        }
+
+        scoreboard.entries.retain(|e| teams.contains_key(&e.client_id));
+        for (client_id, team) in teams {
+            match scoreboard.get_mut(&client_id) {
+                Some(entry) => entry.team = team,
+                None => scoreboard.entries.push(ScoreEntry::new(client_id, team))
+            }
+        }
+        scoreboard.sort();
+    }
+}
+
+fn scoreboard_death_system(
+    mut query: Query<&mut Scoreboard>,
+    mut deaths: EventReader<DeathEvent>
+) {
+    for death in deaths.read() {
+        for mut scoreboard in query.iter_mut() {
+            // read only so that other rooms are not replicated again
+            let listed = [death.killer, death.victim].iter()
+            .chain(death.assists.iter())
+            .any(|id| scoreboard.get(id).is_some());
+            if !listed {
+                continue;
+            }
+
+            if let Some(entry) = scoreboard.get_mut(&death.killer) {
+                entry.kills += 1;
+            }
+            if let Some(entry) = scoreboard.get_mut(&death.victim) {
+                entry.deaths += 1;
+            }
+            for assist in death.assists.iter() {
+                if let Some(entry) = scoreboard.get_mut(assist) {
+                    entry.assists += 1;
+                }
+            }
+            scoreboard.sort();
+        }
+    }
+}
+
+fn notify_death_system(
+    groups: Query<&NotificationGroup>,
+    player_entities: Res<PlayerEntityMap>,
+    mut deaths: EventReader<DeathEvent>,
+    mut notices: EventWriter<Notify<DeathNotice>>
+) {
+    let group = |client_id: &ClientId| player_entities.get(client_id)
+    .and_then(|e| groups.get(*e).ok())
+    .copied();
+
+    for death in deaths.read() {
+        let notice = DeathNotice{
+            victim: death.victim,
+            killer: death.killer
+        };
+        let victim_group = group(&death.victim);
+        match victim_group {
+            Some(g) => notices.send(Notify::new(Recipients::Group(g), notice.clone())),
+            None => notices.send(Notify::new(Recipients::One(death.victim), notice.clone()))
+        };
+        // teammates already got it
+        if victim_group.is_none() || group(&death.killer) != victim_group {
+            notices.send(Notify::new(Recipients::One(death.killer), notice));
+        }
+    }
+}
+
+fn receive_death_notice_system(
+    client: Res<Client>,
+    mut notices: EventReader<DeathNotice>
+) {
+    for notice in notices.read() {
+        if notice.victim.get() == client.id() {
+            info!("you were killed by: {:?}", notice.killer);
+        } else if notice.killer.get() == client.id() {
+            info!("you killed: {:?}", notice.victim);
+        } else {
+            info!("teammate: {:?} was killed by: {:?}", notice.victim, notice.killer);
+        }
+    }
+}
+
+fn scoreboard_ping_system(
+    mut query: Query<&mut Scoreboard>,
+    config: Res<ScoreConfig>,
+    stats: Res<NetworkStats>,
+    time: Res<Time<Real>>,
+    mut last_update: Local<f32>
+) {
+    let now = time.elapsed_seconds();
+    if now - *last_update < config.ping_update_seconds {
+        return;
+    }
+    *last_update = now;
+
+    for mut scoreboard in query.iter_mut() {
+        let pings = scoreboard.entries.iter()
+        .map(|e| stats.get(&e.client_id)
+            .map(|c| (c.latest().rtt * 1000.0).round() as u32)
+            .unwrap_or_default()
+        )
+        .collect::<Vec<_>>();
+        if scoreboard.entries.iter().zip(pings.iter()).all(|(e, p)| e.ping_ms == *p) {
+            continue;
+        }
+        for (entry, ping) in scoreboard.entries.iter_mut().zip(pings) {
+            entry.ping_ms = ping;
+        }
+    }
+}
+
+// every match starts from zero
+fn reset_scores_system(
+    mut query: Query<&mut Scoreboard>,
+    mut changes: EventReader<MatchPhaseChanged>
+) {
+    if !changes.read().any(|c| c.to == MatchPhase::Countdown) {
+        return;
+    }
+    for mut scoreboard in query.iter_mut() {
+        for entry in scoreboard.entries.iter_mut() {
+            entry.kills = 0;
+            entry.deaths = 0;
+            entry.assists = 0;
+        }
+        scoreboard.sort();
+    }
+}
+
+// client scoreboard toggled by KeyboardInputActionMap::scoreboard
+pub struct ScoreboardScreenPlugin;
+
+impl Plugin for ScoreboardScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_scoreboard_screen)
+        .add_systems(Update, (
+            toggle_scoreboard_system,
+            update_scoreboard_screen_system
+        ).chain());
+    }
+}
+
+#[derive(Component)]
+struct ScoreboardText;
+
+fn setup_scoreboard_screen(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle{
+            font_size: 24.0,
+            color: Color::WHITE,
+            ..default()
+        })
+        .with_style(Style{
+            position_type: PositionType::Absolute,
+            top: Val::Px(60.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        Visibility::Hidden,
+        ScoreboardText
+    ));
+}
+
+fn toggle_scoreboard_system(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    keyboard_action_map: Res<KeyboardInputActionMap>,
+    mut query: Query<&mut Visibility, With<ScoreboardText>>
+) {
+    if !keyboard.just_pressed(keyboard_action_map.scoreboard) {
+        return;
+    }
+
+    for mut visibility in query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden
+        };
+    }
+}
+
+fn update_scoreboard_screen_system(
+    mut texts: Query<&mut Text, With<ScoreboardText>>,
+    query: Query<&Scoreboard, Changed<Scoreboard>>
+) {
+    let Ok(scoreboard) = query.get_single() else {
+        return;
+    };
+    let Ok(mut text) = texts.get_single_mut() else {
+        return;
+    };
+
+    let mut lines = vec!["player    team  k  d  a  ping".to_string()];
+    for e in scoreboard.iter() {
+        lines.push(format!(
+            "{:<9} {:<5} {:<2} {:<2} {:<2} {}ms",
+            e.client_id.get(),
+            e.team.map(|t| format!("{t:?}")).unwrap_or_else(|| "-".to_string()),
+            e.kills, e.deaths, e.assists, e.ping_ms
+        ));
+    }
+    text.sections[0].value = lines.join("\n");
+}
@@ -0,0 +1,127 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::netstack::{notification::NotificationGroup, recording::RecordingAppExt};
+
+// user_data[20] is requested team on connect, 0 for auto
+pub const TEAM_USER_DATA_INDEX: usize = 20;
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Team {
+    Red,
+    Blue
+}
+
+impl Team {
+    pub const ALL: [Team; 2] = [Team::Red, Team::Blue];
+
+    #[inline]
+    pub fn color(&self) -> Color {
+        match self {
+            Team::Red => Color::rgb(0.9, 0.2, 0.2),
+            Team::Blue => Color::rgb(0.2, 0.4, 0.9)
+        }
+    }
+
+    #[inline]
+    pub fn from_user_data(user_data: &[u8; 256]) -> Option<Self> {
+        match user_data[TEAM_USER_DATA_INDEX] {
+            1 => Some(Team::Red),
+            2 => Some(Team::Blue),
+            _ => None
+        }
+    }
+
+    #[inline]
+    pub fn to_user_data(team: Option<Self>) -> u8 {
+        match team {
+            None => 0,
+            Some(Team::Red) => 1,
+            Some(Team::Blue) => 2
+        }
+    }
+
+    #[inline]
+    pub fn notification_group(&self) -> NotificationGroup {
+        NotificationGroup(*self as u32)
+    }
+
+    // teams spawn on opposite sides of x axis
+    #[inline]
+    fn side(&self) -> f32 {
+        match self {
+            Team::Red => -1.0,
+            Team::Blue => 1.0
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct TeamConfig {
+    pub friendly_fire: bool,
+    // distance of team spawn from the center
+    pub spawn_distance: f32,
+    // distance between members spawned on the same side
+    pub spawn_spacing: f32
+}
+
+impl Default for TeamConfig {
+    fn default() -> Self {
+        Self{
+            friendly_fire: false,
+            spawn_distance: 5.0,
+            spawn_spacing: 2.0
+        }
+    }
+}
+
+impl TeamConfig {
+    // nth member of the team spawns next to the previous one
+    pub fn spawn_point(&self, team: Team, index: usize) -> Vec2 {
+        let offset = (index as f32 / 2.0).ceil() * self.spawn_spacing;
+        let sign = if index % 2 == 0 { 1.0 } else { -1.0 };
+        Vec2::new(team.side() * self.spawn_distance, offset * sign)
+    }
+
+    // first spawn point of the team nobody stands on,
+    // one of the first occupied.len() + 1 points always is
+    pub fn free_spawn_point(&self, team: Team, occupied: &[Vec2]) -> Vec2 {
+        (0..=occupied.len())
+        .map(|i| self.spawn_point(team, i))
+        .find(|p| occupied.iter().all(|o| o.distance(*p) >= self.spawn_spacing * 0.5))
+        .unwrap_or_else(|| self.spawn_point(team, occupied.len()))
+    }
+
+    #[inline]
+    pub fn can_hit(&self, shooter: Option<&Team>, target: Option<&Team>) -> bool {
+        match (shooter, target) {
+            (Some(s), Some(t)) if s == t => self.friendly_fire,
+            _ => true
+        }
+    }
+}
+
+// requested team is granted unless it makes teams unbalanced,
+// otherwise the smaller team
+pub fn assign_team(requested: Option<Team>, members: &HashMap<Team, usize>) -> Team {
+    let count = |team: &Team| members.get(team).copied().unwrap_or_default();
+    let smallest = Team::ALL.iter()
+    .min_by_key(|t| count(t))
+    .copied()
+    .unwrap_or(Team::Red);
+
+    match requested {
+        Some(team) if count(&team) <= count(&smallest) => team,
+        _ => smallest
+    }
+}
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TeamConfig>()
+        .replicate::<Team>()
+        .record_component::<Team>();
+    }
+}
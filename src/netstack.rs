@@ -4,3 +4,22 @@ pub mod error;
 pub mod components;
 pub mod resources;
 pub mod events;
+pub mod client_event;
+pub mod rate_limit;
+pub mod input;
+pub mod sequence;
+pub mod time_sync;
+pub mod tick;
+pub mod transport;
+pub mod loopback;
+pub mod conditioner;
+pub mod recording;
+pub mod stats;
+pub mod metrics;
+pub mod lifecycle;
+pub mod admin;
+pub mod room;
+pub mod visibility;
+pub mod priority;
+pub mod notification;
+pub mod connection;
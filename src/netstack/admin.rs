@@ -0,0 +1,502 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration
+};
+use bevy::{prelude::*, utils::{HashSet, Uuid}};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::transport::NetcodeServerTransport;
+use anyhow::{anyhow, bail};
+use super::{
+    components::{NetworkPlayer, ServerNetworkPlayerInfo},
+    error::NetstackError,
+    lifecycle::{DisconnectClient, DisconnectNotice, ShutdownHandle},
+    metrics::NetstackMetrics,
+    loopback::LoopbackServerTransport,
+    recording::SessionRecorder,
+    server::{client_user_data, handle_server_event_system, Server},
+    stats::NetworkStats,
+    tick::SimulationTickConfig
+};
+
+const ADMIN_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+const ADMIN_AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Resource, Clone)]
+pub struct AdminConfig {
+    // tcp console, first line has to be "auth <password>"
+    pub listen_addr: Option<SocketAddr>,
+    pub password: String,
+    // commands from stdin of the server process,
+    // first line has to be "auth <password>" as well
+    pub stdin: bool,
+    // stdin skips authentication, only for a terminal the operator owns
+    pub stdin_without_auth: bool
+}
+
+// handler gets arguments after the command name and returns the reply
+pub type AdminCommandHandler = fn(&mut World, &[&str]) -> anyhow::Result<String>;
+
+struct AdminCommand {
+    usage: &'static str,
+    handler: AdminCommandHandler
+}
+
+#[derive(Resource, Default)]
+pub struct AdminCommands(BTreeMap<&'static str, AdminCommand>);
+
+struct AdminRequest {
+    line: String,
+    reply: mpsc::Sender<String>
+}
+
+// receiving end of console threads
+#[derive(Resource)]
+struct AdminConsole(Mutex<mpsc::Receiver<AdminRequest>>);
+
+#[derive(Resource, Default)]
+pub struct BanList {
+    client_ids: HashSet<u64>,
+    uuids: HashSet<Uuid>
+}
+
+impl BanList {
+    #[inline]
+    pub fn is_banned(&self, client_id: u64, uuid: &Uuid) -> bool {
+        self.client_ids.contains(&client_id) || self.uuids.contains(uuid)
+    }
+}
+
+pub trait AdminCommandAppExt {
+    fn add_admin_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: AdminCommandHandler
+    ) -> &mut Self;
+}
+
+impl AdminCommandAppExt for App {
+    fn add_admin_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: AdminCommandHandler
+    ) -> &mut Self {
+        self.world.get_resource_or_insert_with(AdminCommands::default)
+        .0.insert(name, AdminCommand{ usage, handler });
+        self
+    }
+}
+
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AdminCommands>()
+        .init_resource::<BanList>()
+        .add_admin_command("help", "help", help_command)
+        .add_admin_command("players", "players", players_command)
+        .add_admin_command("kick", "kick <client id or uuid> [reason]", kick_command)
+        .add_admin_command("ban", "ban <client id or uuid> [reason]", ban_command)
+        .add_admin_command("unban", "unban <client id or uuid>", unban_command)
+        .add_admin_command("record", "record <on|off>", record_command)
+        .add_admin_command("tickrate", "tickrate <hz> [replication interval]", tick_rate_command)
+        .add_admin_command("shutdown", "shutdown", shutdown_command)
+        .add_systems(Startup,
+            setup_admin.run_if(resource_exists::<AdminConfig>)
+        )
+        .add_systems(Update,
+            admin_command_system.run_if(resource_exists::<AdminConsole>)
+        )
+        // banned players are never spawned
+        .add_systems(Update,
+            reject_banned_system
+            .before(handle_server_event_system)
+            .run_if(resource_exists::<Server>)
+        );
+    }
+}
+
+fn setup_admin(
+    mut commands: Commands,
+    config: Res<AdminConfig>,
+    mut errors: EventWriter<NetstackError>
+) {
+    let (sender, receiver) = mpsc::channel();
+    if let Some(listen_addr) = config.listen_addr {
+        match spawn_tcp_console(listen_addr, config.password.clone(), sender.clone()) {
+            Ok(_) => info!("admin console is listening at {listen_addr}"),
+            Err(e) => {
+                errors.send(NetstackError(e));
+            }
+        }
+    }
+    if config.stdin {
+        if config.stdin_without_auth {
+            warn!("admin stdin console is not authenticated");
+        }
+        let password = (!config.stdin_without_auth).then(|| config.password.clone());
+        if let Err(e) = spawn_stdin_console(password, sender) {
+            errors.send(NetstackError(e));
+        }
+    }
+
+    commands.insert_resource(AdminConsole(Mutex::new(receiver)));
+    commands.remove_resource::<AdminConfig>();
+}
+
+fn spawn_tcp_console(
+    listen_addr: SocketAddr,
+    password: String,
+    sender: mpsc::Sender<AdminRequest>
+) -> anyhow::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(listen_addr)?;
+    let handle = thread::Builder::new()
+    .name("admin console".to_string())
+    .spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("admin console listener: {e}");
+                    continue;
+                }
+            };
+            let password = password.clone();
+            let sender = sender.clone();
+            // one operator at a time is typical, but do not block others
+            let _ = thread::Builder::new()
+            .name("admin session".to_string())
+            .spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = tcp_session(stream, &password, &sender) {
+                    debug!("admin session {peer:?} ended: {e}");
+                }
+            });
+        }
+    })?;
+    Ok(handle)
+}
+
+fn tcp_session(
+    mut stream: TcpStream,
+    password: &str,
+    sender: &mpsc::Sender<AdminRequest>
+) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(ADMIN_AUTH_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    if !is_authenticated(&line, password) {
+        writeln!(stream, "authentication failed")?;
+        warn!("admin console authentication failed from {:?}", stream.peer_addr().ok());
+        return Ok(());
+    }
+    writeln!(stream, "ok")?;
+    info!("admin console session from {:?}", stream.peer_addr().ok());
+    stream.set_read_timeout(None)?;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        if command == "quit" {
+            return Ok(());
+        }
+
+        let reply = request(sender, command)?;
+        writeln!(stream, "{reply}")?;
+    }
+}
+
+// without password every line is a command
+fn spawn_stdin_console(
+    password: Option<String>,
+    sender: mpsc::Sender<AdminRequest>
+) -> anyhow::Result<thread::JoinHandle<()>> {
+    let handle = thread::Builder::new()
+    .name("admin stdin".to_string())
+    .spawn(move || {
+        let mut lines = io::stdin().lock().lines();
+        if let Some(password) = password {
+            let line = lines.next().and_then(|l| l.ok()).unwrap_or_default();
+            if !is_authenticated(&line, &password) {
+                eprintln!("authentication failed");
+                warn!("admin stdin console authentication failed");
+                return;
+            }
+            println!("ok");
+        }
+        for line in lines {
+            let Ok(line) = line else {
+                return;
+            };
+            let command = line.trim();
+            if command.is_empty() {
+                continue;
+            }
+            match request(&sender, command) {
+                Ok(reply) => println!("{reply}"),
+                Err(e) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            }
+        }
+    })?;
+    Ok(handle)
+}
+
+fn request(sender: &mpsc::Sender<AdminRequest>, line: &str) -> anyhow::Result<String> {
+    let (reply, replies) = mpsc::channel();
+    sender.send(AdminRequest{
+        line: line.to_string(),
+        reply
+    })
+    .map_err(|_| anyhow!("server is not running"))?;
+    Ok(replies.recv_timeout(ADMIN_REPLY_TIMEOUT)?)
+}
+
+// first line of a console session
+fn is_authenticated(line: &str, password: &str) -> bool {
+    line.trim()
+    .strip_prefix("auth ")
+    .is_some_and(|p| constant_time_eq(p.as_bytes(), password.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn admin_command_system(world: &mut World) {
+    let requests = {
+        let console = world.resource::<AdminConsole>();
+        let receiver = match console.0.lock() {
+            Ok(r) => r,
+            Err(poisoned) => poisoned.into_inner()
+        };
+        receiver.try_iter().collect::<Vec<_>>()
+    };
+
+    for request in requests {
+        let reply = match execute(world, &request.line) {
+            Ok(reply) => reply,
+            Err(e) => format!("error: {e}")
+        };
+        info!("admin: {} -> {}", request.line, reply.lines().next().unwrap_or_default());
+        // console may have been closed
+        let _ = request.reply.send(reply);
+    }
+}
+
+fn execute(world: &mut World, line: &str) -> anyhow::Result<String> {
+    let mut parts = line.split_whitespace();
+    let name = parts.next().ok_or_else(|| anyhow!("empty command"))?;
+    let args = parts.collect::<Vec<_>>();
+    let handler = world.resource::<AdminCommands>().0.get(name)
+    .map(|c| c.handler)
+    .ok_or_else(|| anyhow!("unknown command: {name}, try help"))?;
+    handler(world, &args)
+}
+
+// client id or uuid
+enum PlayerTarget {
+    ClientId(u64),
+    Uuid(Uuid)
+}
+
+fn parse_target(arg: Option<&&str>) -> anyhow::Result<PlayerTarget> {
+    let arg = arg.ok_or_else(|| anyhow!("client id or uuid is required"))?;
+    if let Ok(client_id) = arg.parse::<u64>() {
+        return Ok(PlayerTarget::ClientId(client_id));
+    }
+    Ok(PlayerTarget::Uuid(Uuid::parse_str(arg)?))
+}
+
+fn find_player(world: &mut World, target: &PlayerTarget) -> Option<(u64, Uuid)> {
+    let mut query = world.query::<(&NetworkPlayer, &ServerNetworkPlayerInfo)>();
+    query.iter(world)
+    .map(|(p, info)| (p.client_id().get(), *info.uuid()))
+    .find(|(client_id, uuid)| match target {
+        PlayerTarget::ClientId(id) => id == client_id,
+        PlayerTarget::Uuid(u) => u == uuid
+    })
+}
+
+#[inline]
+fn reason(args: &[&str]) -> String {
+    if args.is_empty() {
+        "kicked by admin".to_string()
+    } else {
+        args.join(" ")
+    }
+}
+
+// notifies the reason and disconnects
+pub fn kick_client(world: &mut World, client_id: u64, reason: String) {
+    world.send_event(DisconnectClient{
+        client_id: ClientId::new(client_id),
+        notice: DisconnectNotice{ reason, rejected: false }
+    });
+    world.resource_mut::<NetstackMetrics>().count_kick();
+}
+
+fn help_command(world: &mut World, _: &[&str]) -> anyhow::Result<String> {
+    let commands = world.resource::<AdminCommands>();
+    Ok(commands.0.values().map(|c| c.usage).collect::<Vec<_>>().join("\n"))
+}
+
+fn players_command(world: &mut World, _: &[&str]) -> anyhow::Result<String> {
+    let mut query = world.query::<(&NetworkPlayer, &ServerNetworkPlayerInfo)>();
+    let stats = world.resource::<NetworkStats>();
+    let mut lines = query.iter(world)
+    .map(|(p, info)| {
+        let rtt = stats.get(&p.client_id())
+        .map(|c| format!("{:.1}ms", c.latest().rtt * 1000.0))
+        .unwrap_or_else(|| "-".to_string());
+        format!("{} {} rtt: {}", p.client_id().get(), info.uuid(), rtt)
+    })
+    .collect::<Vec<_>>();
+    lines.sort();
+    lines.insert(0, format!("{} players", lines.len()));
+    Ok(lines.join("\n"))
+}
+
+fn kick_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let target = parse_target(args.first())?;
+    let (client_id, uuid) = find_player(world, &target)
+    .ok_or_else(|| anyhow!("player is not found"))?;
+    kick_client(world, client_id, reason(&args[1..]));
+    Ok(format!("kicked {client_id} {uuid}"))
+}
+
+fn ban_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let target = parse_target(args.first())?;
+    let player = find_player(world, &target);
+    let mut bans = world.resource_mut::<BanList>();
+    match (&target, player) {
+        (_, Some((client_id, uuid))) => {
+            bans.client_ids.insert(client_id);
+            bans.uuids.insert(uuid);
+        }
+        (PlayerTarget::ClientId(client_id), None) => {
+            bans.client_ids.insert(*client_id);
+        }
+        (PlayerTarget::Uuid(uuid), None) => {
+            bans.uuids.insert(*uuid);
+        }
+    }
+
+    match player {
+        Some((client_id, uuid)) => {
+            kick_client(world, client_id, reason(&args[1..]));
+            Ok(format!("banned and kicked {client_id} {uuid}"))
+        }
+        None => Ok("banned, player is not connected".to_string())
+    }
+}
+
+fn unban_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let target = parse_target(args.first())?;
+    let mut bans = world.resource_mut::<BanList>();
+    let removed = match target {
+        PlayerTarget::ClientId(client_id) => bans.client_ids.remove(&client_id),
+        PlayerTarget::Uuid(uuid) => bans.uuids.remove(&uuid)
+    };
+    if removed {
+        Ok("unbanned".to_string())
+    } else {
+        bail!("not banned")
+    }
+}
+
+fn record_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let mut recorder = world.get_resource_mut::<SessionRecorder>()
+    .ok_or_else(|| anyhow!("recording is not configured"))?;
+    match args.first() {
+        Some(&"on") => {
+            recorder.start();
+            Ok("recording".to_string())
+        }
+        Some(&"off") => {
+            recorder.stop()?;
+            Ok("stopped recording".to_string())
+        }
+        None => Ok(format!("recording: {}", recorder.is_recording())),
+        Some(arg) => bail!("expected on or off: {arg}")
+    }
+}
+
+// tick based deadlines kept by gameplay are not rescaled,
+// games can replace the command to refuse it while they run
+pub fn tick_rate_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let mut config = world.resource_mut::<SimulationTickConfig>();
+    let Some(tick_rate) = args.first() else {
+        return Ok(format!(
+            "tick rate: {} replication interval: {}",
+            config.tick_rate, config.replication_interval
+        ));
+    };
+
+    let tick_rate = tick_rate.parse::<u16>()?;
+    let replication_interval = match args.get(1) {
+        Some(i) => i.parse::<u16>()?,
+        None => config.replication_interval
+    };
+    if tick_rate == 0 || replication_interval == 0 {
+        bail!("tick rate and replication interval should be positive");
+    }
+
+    // applied and synced to clients by simulation tick plugin
+    config.tick_rate = tick_rate;
+    config.replication_interval = replication_interval;
+    Ok(format!(
+        "tick rate: {} network tick rate: {}",
+        tick_rate, config.network_tick_rate()
+    ))
+}
+
+fn shutdown_command(world: &mut World, _: &[&str]) -> anyhow::Result<String> {
+    world.resource::<ShutdownHandle>().request();
+    Ok("shutting down".to_string())
+}
+
+fn reject_banned_system(
+    mut server_events: EventReader<ServerEvent>,
+    bans: Res<BanList>,
+    netcode_server: Option<Res<NetcodeServerTransport>>,
+    loopback_server: Option<Res<LoopbackServerTransport>>,
+    mut disconnects: EventWriter<DisconnectClient>
+) {
+    for e in server_events.read() {
+        let ServerEvent::ClientConnected { client_id } = e else {
+            continue;
+        };
+        // missing user data is reported when spawning
+        let uuid = client_user_data(netcode_server.as_deref(), loopback_server.as_deref(), client_id)
+        .and_then(|u| Uuid::from_slice(&u[0..16]).ok())
+        .unwrap_or_default();
+        if !bans.is_banned(client_id.get(), &uuid) {
+            continue;
+        }
+
+        warn!("banned client: {client_id:?} id: {uuid} is rejected");
+        disconnects.send(DisconnectClient{
+            client_id: *client_id,
+            notice: DisconnectNotice{ reason: "banned".to_string(), rejected: true }
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use std::{net::{IpAddr, UdpSocket, SocketAddr}, time::SystemTime};
 use bevy::prelude::*;
-use bevy_replicon::prelude::*;
+use bevy_replicon::{client::diagnostics::ClientStats, prelude::*};
 use bevy_replicon_renet::{
     renet::{
         transport::{ClientAuthentication, ConnectToken, NetcodeClientTransport}, 
@@ -11,7 +11,17 @@ use bevy_replicon_renet::{
 use bevy_replicon_snap::RepliconSnapPlugin;
 use super::{
     components::NetworkPlayer, 
-    error::{on_transport_error_system, NetstackError}
+    conditioner::{spawn_udp_link_conditioner, LinkConditionsHandle, UdpLinkConditioner},
+    connection::{client_connection_state_system, ClientConnectionPlugin, ClientConnectionStarted},
+    error::{on_transport_error_system, NetstackError},
+    lifecycle::LifecyclePlugin,
+    loopback::{LoopbackClientPlugin, LoopbackClientTransport},
+    room::RoomPlugin,
+    sequence::InputSequencer,
+    stats::NetworkStatsPlugin,
+    tick::SimulationTickPlugin,
+    time_sync::TimeSyncPlugin,
+    transport::NetstackTransport
 };
 
 #[derive(Resource)]
@@ -25,6 +35,7 @@ pub struct ClientConfig {
     pub private_key: [u8; 32],
     pub user_data: [u8; 256],
     pub token_expire_seconds: u64,
+    pub transport: NetstackTransport
 }
 
 #[derive(Resource)]
@@ -46,8 +57,22 @@ impl Plugin for ClientNetstackPlugin {
             RepliconRenetPlugins.build().disable::<RepliconRenetServerPlugin>(),
             RepliconSnapPlugin
         ))
+        .add_plugins((
+            TimeSyncPlugin,
+            SimulationTickPlugin,
+            LoopbackClientPlugin,
+            NetworkStatsPlugin,
+            LifecyclePlugin,
+            RoomPlugin,
+            ClientConnectionPlugin
+        ))
         .add_event::<NetstackError>()
+        .init_resource::<InputSequencer>()
+        .init_resource::<ClientStats>()
         .replicate::<NetworkPlayer>()
+        .add_systems(PreUpdate,
+            reset_input_sequencer_system.after(client_connection_state_system)
+        )
         .add_systems(Update, on_transport_error_system);
     }
 }
@@ -56,6 +81,7 @@ pub fn setup_client(
     mut commands: Commands,
     net_channels: Res<RepliconChannels>,
     config: Res<ClientConfig>,
+    link_conditions: Option<Res<LinkConditionsHandle>>,
     mut errors: EventWriter<NetstackError>
 ) {
     let renet_client = RenetClient::new(ConnectionConfig{
@@ -64,36 +90,84 @@ pub fn setup_client(
         ..default()
     });
 
-    let netcode_transport = match setup_transport(&config) {
-        Ok(t) => t,
-        Err(e) => {
-            errors.send(NetstackError(e));
-            return;
+    match &config.transport {
+        NetstackTransport::Udp => {
+            let (netcode_transport, relay) = match setup_transport(
+                &config,
+                link_conditions.as_deref()
+            ) {
+                Ok(t) => t,
+                Err(e) => {
+                    errors.send(NetstackError(e));
+                    return;
+                }
+            };
+            commands.insert_resource(netcode_transport);
+            if let Some(relay) = relay {
+                commands.insert_resource(relay);
+            }
         }
-    };
+        NetstackTransport::Loopback(network) => {
+            // hub conditions are shared by both ends
+            if let Some(link_conditions) = link_conditions {
+                network.use_link_conditions(link_conditions.clone());
+            }
+            commands.insert_resource(LoopbackClientTransport::new(
+                network.clone(), 
+                config.client_id, 
+                config.user_data
+            ));
+        }
+    }
     let client = Client(config.client_id);
 
     commands.remove_resource::<ClientConfig>();
     commands.insert_resource(client);
     commands.insert_resource(renet_client);
-    commands.insert_resource(netcode_transport);
 }
 
-fn setup_transport(config: &ClientConfig) 
--> anyhow::Result<NetcodeClientTransport> {
+// sequence starts over for each connection
+fn reset_input_sequencer_system(
+    mut started: EventReader<ClientConnectionStarted>,
+    mut sequencer: ResMut<InputSequencer>
+) {
+    if started.read().last().is_some() {
+        *sequencer = default();
+    }
+}
+
+fn setup_transport(
+    config: &ClientConfig,
+    link_conditions: Option<&LinkConditionsHandle>
+) -> anyhow::Result<(NetcodeClientTransport, Option<UdpLinkConditioner>)> {
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+    let server_addr = SocketAddr::new(config.server_addr, config.server_port);
     let socket = UdpSocket::bind((config.client_addr, 0))?;
+    // client sends to a local relay in front of it and the server is listed second,
+    // so that the token still holds the server's public address
+    let (server_addresses, relay) = match link_conditions {
+        Some(link_conditions) => {
+            let relay = spawn_udp_link_conditioner(
+                SocketAddr::new(config.client_addr, 0),
+                server_addr,
+                link_conditions.clone()
+            )?;
+            warn!("link conditioner is enabled: {:?}", link_conditions.get());
+            (vec![relay.public_addr(), server_addr], Some(relay))
+        }
+        None => (vec![server_addr], None)
+    };
     let connect_token = ConnectToken::generate(
         current_time,
         config.protocol_id,
         config.token_expire_seconds,
         config.client_id,
         config.timeout_seconds,
-        vec![SocketAddr::new(config.server_addr, config.server_port)],
+        server_addresses,
         Some(&config.user_data),
         &config.private_key
     )?;
     let auth = ClientAuthentication::Secure {connect_token};
     let netcode_transport = NetcodeClientTransport::new(current_time, auth, socket)?;
-    Ok(netcode_transport)
+    Ok((netcode_transport, relay))
 }
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use super::server::{Server, ServerNetstackSet};
+
+// client events which passed every ServerNetstackSet filter,
+// gameplay reads these instead of FromClient<E>
+#[derive(Event, Clone, Debug)]
+pub struct AcceptedFromClient<E> {
+    pub client_id: ClientId,
+    pub event: E
+}
+
+// client events of the current update on their way through ServerNetstackSet,
+// filters take and push back what they let through
+#[derive(Resource)]
+pub struct ClientEventQueue<E> {
+    events: Vec<(ClientId, E)>
+}
+
+impl<E> Default for ClientEventQueue<E> {
+    fn default() -> Self {
+        Self{
+            events: default()
+        }
+    }
+}
+
+impl<E> ClientEventQueue<E> {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &(ClientId, E)> {
+        self.events.iter()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    #[inline]
+    pub fn push(&mut self, client_id: ClientId, event: E) {
+        self.events.push((client_id, event));
+    }
+
+    #[inline]
+    pub fn take(&mut self) -> Vec<(ClientId, E)> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+pub trait ClientEventAppExt {
+    // event should be registered with add_client_event or use_client_event_snapshots,
+    // calling more than once is fine
+    fn accept_client_event<E: Event + Clone>(&mut self) -> &mut Self;
+}
+
+impl ClientEventAppExt for App {
+    fn accept_client_event<E: Event + Clone>(&mut self) -> &mut Self {
+        if self.world.contains_resource::<ClientEventQueue<E>>() {
+            return self;
+        }
+
+        self.init_resource::<ClientEventQueue<E>>()
+        .add_event::<AcceptedFromClient<E>>()
+        .add_systems(PreUpdate, (
+            collect_client_events_system::<E>
+            .in_set(ServerNetstackSet::ClientEventCollect),
+            accept_client_events_system::<E>
+            .in_set(ServerNetstackSet::ClientEventAccept)
+        ).run_if(resource_exists::<Server>))
+    }
+}
+
+// FromClient<E> is left as is for other readers,
+// FixedUpdate systems may not have read it yet
+fn collect_client_events_system<E: Event + Clone>(
+    mut events: EventReader<FromClient<E>>,
+    mut queue: ResMut<ClientEventQueue<E>>
+) {
+    for FromClient { client_id, event } in events.read() {
+        queue.push(*client_id, event.clone());
+    }
+}
+
+fn accept_client_events_system<E: Event>(
+    mut queue: ResMut<ClientEventQueue<E>>,
+    mut accepted: EventWriter<AcceptedFromClient<E>>
+) {
+    if queue.is_empty() {
+        return;
+    }
+    accepted.send_batch(queue.take().into_iter()
+        .map(|(client_id, event)| AcceptedFromClient{ client_id, event })
+    );
+}
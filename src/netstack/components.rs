@@ -26,21 +26,35 @@ impl NetworkPlayer {
 // component with player info only for server
 #[derive(Component)]
 pub struct ServerNetworkPlayerInfo {
-    uuid: Uuid
+    uuid: Uuid,
+    display_name: String
 }
 
 impl ServerNetworkPlayerInfo {
     #[inline]
     pub fn new(uuid: Uuid) -> Self {
         Self{
-            uuid
+            uuid,
+            display_name: String::new()
         }
     }
 
+    #[inline]
+    pub fn with_display_name(mut self, display_name: String) -> Self {
+        self.display_name = display_name;
+        self
+    }
+
     #[inline]
     pub fn uuid(&self) -> &Uuid {
         &self.uuid
     }
+
+    // empty when session has no name
+    #[inline]
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
 }
 
 // bundle for player controlled entities. each player can have many
@@ -0,0 +1,341 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    str::FromStr,
+    sync::{atomic::{AtomicBool, Ordering as AtomicOrdering}, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant}
+};
+use bevy::{prelude::*, utils::HashMap};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use anyhow::{anyhow, bail};
+
+// conditions for one direction
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LinkConditionerConfig {
+    pub latency_ms: f32,
+    // added or subtracted randomly from latency
+    pub jitter_ms: f32,
+    // probability 0.0 - 1.0
+    pub loss: f32,
+    pub duplicate: f32,
+    // held back by reorder_delay_ms so that later packets overtake
+    pub reorder: f32,
+    pub reorder_delay_ms: f32
+}
+
+impl LinkConditionerConfig {
+    #[inline]
+    pub fn is_pass_through(&self) -> bool {
+        self.latency_ms <= 0.0
+        && self.jitter_ms <= 0.0
+        && self.loss <= 0.0
+        && self.duplicate <= 0.0
+        && self.reorder <= 0.0
+    }
+}
+
+// "latency=100,jitter=20,loss=0.05,duplicate=0.01,reorder=0.01,reorder_delay=50"
+impl FromStr for LinkConditionerConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        for pair in s.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=')
+            .ok_or_else(|| anyhow!("expected key=value: {pair}"))?;
+            let value = value.parse::<f32>()?;
+            match key {
+                "latency" => config.latency_ms = value,
+                "jitter" => config.jitter_ms = value,
+                "loss" => config.loss = value,
+                "duplicate" => config.duplicate = value,
+                "reorder" => config.reorder = value,
+                "reorder_delay" => config.reorder_delay_ms = value,
+                _ => bail!("unknown link condition: {key}")
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LinkConditions {
+    // client to server
+    pub upstream: LinkConditionerConfig,
+    // server to client
+    pub downstream: LinkConditionerConfig
+}
+
+// shared with transports, can be changed at runtime
+#[derive(Resource, Clone, Default)]
+pub struct LinkConditionsHandle(Arc<Mutex<LinkConditions>>);
+
+impl LinkConditionsHandle {
+    #[inline]
+    pub fn new(conditions: LinkConditions) -> Self {
+        Self(Arc::new(Mutex::new(conditions)))
+    }
+
+    #[inline]
+    pub fn get(&self) -> LinkConditions {
+        match self.0.lock() {
+            Ok(c) => *c,
+            Err(poisoned) => *poisoned.into_inner()
+        }
+    }
+
+    #[inline]
+    pub fn set(&self, conditions: LinkConditions) {
+        match self.0.lock() {
+            Ok(mut c) => *c = conditions,
+            Err(poisoned) => *poisoned.into_inner() = conditions
+        }
+    }
+}
+
+struct DelayedPacket<T> {
+    release: Duration,
+    order: u64,
+    packet: T
+}
+
+impl<T> PartialEq for DelayedPacket<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.release == other.release && self.order == other.order
+    }
+}
+
+impl<T> Eq for DelayedPacket<T> {}
+
+impl<T> PartialOrd for DelayedPacket<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// reversed for min heap, earliest release first
+impl<T> Ord for DelayedPacket<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.release.cmp(&self.release)
+        .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+// delays, drops, duplicates and reorders packets of one direction.
+// time is given by caller so that it follows app time in tests
+pub struct LinkConditioner<T> {
+    rng: StdRng,
+    queue: BinaryHeap<DelayedPacket<T>>,
+    next_order: u64
+}
+
+impl<T: Clone> Default for LinkConditioner<T> {
+    fn default() -> Self {
+        Self::new(StdRng::from_entropy())
+    }
+}
+
+impl<T: Clone> LinkConditioner<T> {
+    #[inline]
+    pub fn new(rng: StdRng) -> Self {
+        Self{
+            rng,
+            queue: default(),
+            next_order: 0
+        }
+    }
+
+    #[inline]
+    pub fn with_seed(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn send(&mut self, config: &LinkConditionerConfig, now: Duration, packet: T) {
+        if config.is_pass_through() {
+            self.push(now, packet);
+            return;
+        }
+
+        if self.rng.gen::<f32>() < config.loss {
+            return;
+        }
+
+        let copies = if self.rng.gen::<f32>() < config.duplicate { 2 } else { 1 };
+        for _ in 0..copies {
+            let jitter = config.jitter_ms * self.rng.gen_range(-1.0..=1.0);
+            let mut delay_ms = (config.latency_ms + jitter).max(0.0);
+            if self.rng.gen::<f32>() < config.reorder {
+                delay_ms += config.reorder_delay_ms.max(0.0);
+            }
+
+            let release = now + Duration::from_secs_f32(delay_ms / 1000.0);
+            self.push(release, packet.clone());
+        }
+    }
+
+    pub fn receive(&mut self, now: Duration) -> Vec<T> {
+        let mut ready = vec![];
+        while self.queue.peek().is_some_and(|p| p.release <= now) {
+            if let Some(p) = self.queue.pop() {
+                ready.push(p.packet);
+            }
+        }
+        ready
+    }
+
+    fn push(&mut self, release: Duration, packet: T) {
+        self.queue.push(DelayedPacket{
+            release,
+            order: self.next_order,
+            packet
+        });
+        self.next_order += 1;
+    }
+}
+
+struct RelayPeer {
+    upstream: UdpSocket,
+    last_seen: Duration
+}
+
+const RELAY_PEER_TIMEOUT: Duration = Duration::from_secs(60);
+const RELAY_SLEEP: Duration = Duration::from_millis(1);
+const RELAY_MAX_PACKET_SIZE: usize = 1500;
+
+// running udp relay, the thread is stopped and joined on drop
+#[derive(Resource)]
+pub struct UdpLinkConditioner {
+    public_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl UdpLinkConditioner {
+    // actual address when bound to port 0
+    #[inline]
+    pub fn public_addr(&self) -> SocketAddr {
+        self.public_addr
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, AtomicOrdering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                error!("link conditioner thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for UdpLinkConditioner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// udp relay in front of the server socket, or in front of a client when bound on client side.
+// clients send to public_addr and each of them gets its own upstream socket
+// so that the server still sees one address per client
+pub fn spawn_udp_link_conditioner(
+    public_addr: SocketAddr,
+    server_addr: SocketAddr,
+    conditions: LinkConditionsHandle
+) -> anyhow::Result<UdpLinkConditioner> {
+    let public = UdpSocket::bind(public_addr)?;
+    public.set_nonblocking(true)?;
+    let public_addr = public.local_addr()?;
+    let bind_ip = public_addr.ip();
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+
+    let thread = thread::Builder::new()
+    .name("link conditioner".to_string())
+    .spawn(move || {
+        let start = Instant::now();
+        let mut peers = HashMap::<SocketAddr, RelayPeer>::new();
+        let mut upstream = LinkConditioner::<(SocketAddr, Vec<u8>)>::default();
+        let mut downstream = LinkConditioner::<(SocketAddr, Vec<u8>)>::default();
+        let mut buffer = [0u8; RELAY_MAX_PACKET_SIZE];
+
+        while !thread_stop.load(AtomicOrdering::Relaxed) {
+            let now = start.elapsed();
+            let link = conditions.get();
+
+            loop {
+                match public.recv_from(&mut buffer) {
+                    Ok((len, client_addr)) => {
+                        if !peers.contains_key(&client_addr) {
+                            match UdpSocket::bind((bind_ip, 0))
+                            .and_then(|s| s.connect(server_addr).map(|_| s))
+                            .and_then(|s| s.set_nonblocking(true).map(|_| s)) {
+                                Ok(s) => {
+                                    peers.insert(client_addr, RelayPeer{
+                                        upstream: s,
+                                        last_seen: now
+                                    });
+                                }
+                                Err(e) => {
+                                    error!("link conditioner failed to open upstream socket: {e}");
+                                    continue;
+                                }
+                            }
+                        }
+                        if let Some(peer) = peers.get_mut(&client_addr) {
+                            peer.last_seen = now;
+                        }
+                        upstream.send(&link.upstream, now, (client_addr, buffer[..len].to_vec()));
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        warn!("link conditioner public socket: {e}");
+                        break;
+                    }
+                }
+            }
+
+            for (client_addr, peer) in peers.iter() {
+                loop {
+                    match peer.upstream.recv(&mut buffer) {
+                        Ok(len) => {
+                            downstream.send(&link.downstream, now, (*client_addr, buffer[..len].to_vec()));
+                        }
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                        Err(e) => {
+                            warn!("link conditioner upstream socket: {e}");
+                            break;
+                        }
+                    }
+                }
+            }
+
+            for (client_addr, packet) in upstream.receive(now) {
+                if let Some(peer) = peers.get(&client_addr) {
+                    if let Err(e) = peer.upstream.send(&packet) {
+                        warn!("link conditioner failed to send to server: {e}");
+                    }
+                }
+            }
+            for (client_addr, packet) in downstream.receive(now) {
+                if let Err(e) = public.send_to(&packet, client_addr) {
+                    warn!("link conditioner failed to send to client: {e}");
+                }
+            }
+
+            peers.retain(|_, p| now.saturating_sub(p.last_seen) < RELAY_PEER_TIMEOUT);
+            thread::sleep(RELAY_SLEEP);
+        }
+    })?;
+    Ok(UdpLinkConditioner{
+        public_addr,
+        stop,
+        thread: Some(thread)
+    })
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{transport::NetcodeTransportError, RenetClient};
+use super::lifecycle::DisconnectNotice;
+
+// driven by renet client, Disconnected is also the state before the first connect
+#[derive(States, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ClientConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    // server refused the connection or it never got through
+    Rejected
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DisconnectReason {
+    // from DisconnectNotice
+    Server(String),
+    // renet or netcode, including disconnects by client itself
+    Transport(String)
+}
+
+// sent on client when the state leaves Connecting or Connected,
+// readable in OnEnter of Disconnected and Rejected
+#[derive(Event, Clone, Debug)]
+pub struct ClientDisconnected {
+    pub reason: DisconnectReason,
+    pub rejected: bool
+}
+
+// sent on client when a new connection starts,
+// resources kept for one connection are reset on it
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ClientConnectionStarted;
+
+pub struct ClientConnectionPlugin;
+
+impl Plugin for ClientConnectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<ClientConnectionState>()
+        .add_event::<ClientDisconnected>()
+        .add_event::<ClientConnectionStarted>()
+        .add_systems(PreUpdate,
+            client_connection_state_system
+            .after(ClientSet::Receive)
+            .run_if(resource_exists::<RenetClient>)
+        );
+    }
+}
+
+pub(crate) fn client_connection_state_system(
+    renet_client: Res<RenetClient>,
+    state: Res<State<ClientConnectionState>>,
+    mut next_state: ResMut<NextState<ClientConnectionState>>,
+    mut notices: EventReader<DisconnectNotice>,
+    mut transport_errors: EventReader<NetcodeTransportError>,
+    mut last_notice: Local<Option<DisconnectNotice>>,
+    mut last_transport_error: Local<Option<String>>,
+    mut disconnections: EventWriter<ClientDisconnected>,
+    mut started: EventWriter<ClientConnectionStarted>
+) {
+    // notice arrives a moment before the disconnect
+    if let Some(notice) = notices.read().last() {
+        warn!("server is disconnecting: {}", notice.reason);
+        *last_notice = Some(notice.clone());
+    }
+    if let Some(e) = transport_errors.read().last() {
+        *last_transport_error = Some(e.to_string());
+    }
+
+    let current = *state.get();
+    let next = if renet_client.is_connected() {
+        ClientConnectionState::Connected
+    } else if renet_client.is_connecting() {
+        ClientConnectionState::Connecting
+    } else {
+        match current {
+            ClientConnectionState::Connecting => ClientConnectionState::Rejected,
+            ClientConnectionState::Connected => match last_notice.as_ref() {
+                Some(notice) if notice.rejected => ClientConnectionState::Rejected,
+                _ => ClientConnectionState::Disconnected
+            },
+            _ => return
+        }
+    };
+    if next == current {
+        return;
+    }
+
+    match next {
+        // loopback can connect within the first frame
+        ClientConnectionState::Connecting | ClientConnectionState::Connected => {
+            // a new connection starts without the previous reasons
+            if current != ClientConnectionState::Connecting {
+                *last_notice = None;
+                *last_transport_error = None;
+                started.send(ClientConnectionStarted);
+            }
+        }
+        ClientConnectionState::Disconnected | ClientConnectionState::Rejected => {
+            let reason = match (last_notice.take(), last_transport_error.take()) {
+                (Some(notice), _) => DisconnectReason::Server(notice.reason),
+                (None, Some(e)) => DisconnectReason::Transport(e),
+                (None, None) => DisconnectReason::Transport(
+                    renet_client.disconnect_reason()
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| "unknown".to_string())
+                )
+            };
+            disconnections.send(ClientDisconnected{
+                reason,
+                rejected: next == ClientConnectionState::Rejected
+            });
+        }
+    }
+    info!("connection: {current:?} -> {next:?}");
+    next_state.set(next);
+}
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use bevy_replicon_renet::renet::transport::NetcodeTransportError;
+use bevy_replicon_renet::renet::transport::{NetcodeError, NetcodeTransportError};
 
 #[derive(Event)]
 pub struct NetstackError(pub anyhow::Error);
@@ -10,11 +10,32 @@ pub fn panic_on_net_error_system(mut error: EventReader<NetstackError>) {
     }
 }
 
+// for apps which keep running on errors
+pub fn log_net_error_system(mut error: EventReader<NetstackError>) {
+    for e in error.read() {
+        error!("netstack error: {}", e.0);
+    }
+}
+
+// kicks, rejects and timeouts are not failures,
+// clients follow them with ClientConnectionState
+#[inline]
+fn is_disconnect(e: &NetcodeTransportError) -> bool {
+    matches!(e,
+        NetcodeTransportError::Renet(_)
+        | NetcodeTransportError::Netcode(NetcodeError::Disconnected(_))
+    )
+}
+
 pub(crate) fn on_transport_error_system(
     mut netcode_errors: EventReader<NetcodeTransportError>,
     mut netstack_errors: EventWriter<NetstackError>
 ) {
     for e in netcode_errors.read() {
+        if is_disconnect(e) {
+            info!("transport disconnected: {e}");
+            continue;
+        }
         netstack_errors.send(NetstackError(anyhow::anyhow!("{e}")));
     }
 } 
@@ -1,16 +1,27 @@
 use bevy::prelude::*;
 use bevy_replicon_snap::snapshots::event_snapshots::IndexedEvent;
 use serde::{Serialize, Deserialize};
+use super::sequence::{InputSequence, SequencedEvent};
 
 #[derive(Event, Serialize, Deserialize, Clone)]
 pub struct NetworkMovement2DEvent {
     pub axis: Vec2,
-    pub index: usize
+    pub sequence: InputSequence,
+    // server tick estimated by client when this input is made
+    pub tick: u32
 }
 
+impl SequencedEvent for NetworkMovement2DEvent {
+    fn sequence(&self) -> InputSequence {
+        self.sequence
+    }
+}
+
+// required by snapshot buffers, not an order after u32 wraps around,
+// inputs are ordered with InputSequence::cmp_wrapping instead
 impl IndexedEvent for NetworkMovement2DEvent {
     fn index(&self) -> usize {
-        self.index
+        self.sequence.get() as usize
     }
 }
 
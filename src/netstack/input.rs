@@ -0,0 +1,329 @@
+use std::{collections::VecDeque, marker::PhantomData};
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::prelude::*;
+use bevy_replicon_snap::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use super::{
+    client::Client,
+    client_event::{AcceptedFromClient, ClientEventAppExt, ClientEventQueue},
+    connection::{client_connection_state_system, ClientConnectionStarted},
+    sequence::{InputSequence, SequencedEvent},
+    server::{Server, ServerNetstackSet}
+};
+
+// inputs sent before but not acknowledged yet, sent every frame until acked
+// so that a lost packet is covered even after the player stops sending
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct RedundantInputs<E> {
+    pub inputs: Vec<E>
+}
+
+// sequence of the latest input the server has applied to the entity,
+// replicated with the state it produced
+#[derive(Component, Serialize, Deserialize)]
+pub struct NetworkInputAck<E> {
+    sequence: Option<InputSequence>,
+    #[serde(skip)]
+    phantom: PhantomData<E>
+}
+
+impl<E> Default for NetworkInputAck<E> {
+    #[inline]
+    fn default() -> Self {
+        Self{
+            sequence: None,
+            phantom: PhantomData
+        }
+    }
+}
+
+impl<E> NetworkInputAck<E> {
+    #[inline]
+    pub fn sequence(&self) -> Option<InputSequence> {
+        self.sequence
+    }
+
+    #[inline]
+    pub fn set(&mut self, sequence: InputSequence) {
+        if self.sequence.is_some_and(|s| !sequence.is_newer_than(&s)) {
+            return;
+        }
+        self.sequence = Some(sequence);
+    }
+}
+
+// client side inputs waiting for acknowledgement
+#[derive(Resource)]
+pub struct UnackedInputs<E> {
+    redundancy: usize,
+    capacity: usize,
+    inputs: VecDeque<E>,
+    acked_sequence: Option<InputSequence>
+}
+
+impl<E: SequencedEvent + Clone> UnackedInputs<E> {
+    #[inline]
+    pub fn new(redundancy: usize, capacity: usize) -> Self {
+        Self{
+            redundancy,
+            capacity,
+            inputs: VecDeque::with_capacity(capacity),
+            acked_sequence: None
+        }
+    }
+
+    #[inline]
+    pub fn acked_sequence(&self) -> Option<InputSequence> {
+        self.acked_sequence
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.inputs.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    // latest unacknowledged inputs, at most redundancy
+    #[inline]
+    pub fn redundant(&self) -> impl Iterator<Item = &E> {
+        let skip = self.inputs.len().saturating_sub(self.redundancy);
+        self.inputs.iter().skip(skip)
+    }
+
+    // inputs and acks of the previous connection mean nothing to the server
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inputs.clear();
+        self.acked_sequence = None;
+    }
+
+    fn push(&mut self, input: E) {
+        if self.inputs.len() >= self.capacity {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back(input);
+    }
+
+    fn ack(&mut self, sequence: InputSequence) {
+        if self.acked_sequence.is_some_and(|s| !sequence.is_newer_than(&s)) {
+            return;
+        }
+
+        self.acked_sequence = Some(sequence);
+        while let Some(front) = self.inputs.front() {
+            if front.sequence().is_newer_than(&sequence) {
+                break;
+            }
+            self.inputs.pop_front();
+        }
+    }
+}
+
+// server side latest received input sequence for each client
+#[derive(Resource)]
+pub struct ReceivedInputs<E> {
+    redundancy: usize,
+    sequences: HashMap<ClientId, InputSequence>,
+    phantom: PhantomData<E>
+}
+
+impl<E> ReceivedInputs<E> {
+    #[inline]
+    pub fn new(redundancy: usize) -> Self {
+        Self{
+            redundancy,
+            sequences: default(),
+            phantom: PhantomData
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, client_id: &ClientId) -> Option<InputSequence> {
+        self.sequences.get(client_id).copied()
+    }
+}
+
+// server side accepted inputs of each client in sequence order,
+// kept until the simulation drains them in FixedUpdate
+#[derive(Resource)]
+pub struct ServerInputQueue<E> {
+    capacity: usize,
+    inputs: HashMap<ClientId, VecDeque<E>>
+}
+
+impl<E> ServerInputQueue<E> {
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        Self{
+            capacity,
+            inputs: default()
+        }
+    }
+
+    #[inline]
+    pub fn len(&self, client_id: &ClientId) -> usize {
+        self.inputs.get(client_id).map_or(0, |q| q.len())
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&ClientId, usize)> {
+        self.inputs.iter().map(|(client_id, q)| (client_id, q.len()))
+    }
+
+    #[inline]
+    pub fn drain(&mut self, client_id: &ClientId) -> impl Iterator<Item = E> + '_ {
+        self.inputs.get_mut(client_id).into_iter().flat_map(|q| q.drain(..))
+    }
+
+    // oldest inputs are dropped when the simulation falls behind
+    fn push(&mut self, client_id: ClientId, input: E) {
+        let queue = self.inputs.entry(client_id).or_default();
+        if queue.len() >= self.capacity {
+            queue.pop_front();
+        }
+        queue.push_back(input);
+    }
+}
+
+pub trait RedundantInputAppExt {
+    // event should be registered with add_client_event or use_client_event_snapshots
+    // server should drain ServerInputQueue<E> and update NetworkInputAck<E>
+    // on the entity when it applies inputs
+    fn use_redundant_inputs<E>(&mut self, redundancy: usize, capacity: usize) -> &mut Self
+    where E: Event + SequencedEvent + Serialize + DeserializeOwned + Clone;
+}
+
+impl RedundantInputAppExt for App {
+    fn use_redundant_inputs<E>(&mut self, redundancy: usize, capacity: usize) -> &mut Self
+    where E: Event + SequencedEvent + Serialize + DeserializeOwned + Clone {
+        self.accept_client_event::<E>()
+        .insert_resource(UnackedInputs::<E>::new(redundancy, capacity))
+        .insert_resource(ReceivedInputs::<E>::new(redundancy))
+        .insert_resource(ServerInputQueue::<E>::new(capacity))
+        .add_client_event::<RedundantInputs<E>>(ChannelKind::Unreliable)
+        .replicate::<NetworkInputAck<E>>()
+        .add_systems(PreUpdate, (
+            merge_redundant_inputs_system::<E>,
+            received_inputs_disconnect_system::<E>
+        )
+            .chain()
+            .in_set(ServerNetstackSet::ClientInputMerge)
+            .run_if(resource_exists::<Server>)
+        )
+        .add_systems(PreUpdate,
+            queue_accepted_inputs_system::<E>
+            .in_set(ServerNetstackSet::ClientEventObserve)
+            .run_if(resource_exists::<Server>)
+        )
+        .add_systems(PreUpdate, (
+            reset_unacked_inputs_system::<E>,
+            receive_input_ack_system::<E>
+        )
+            .chain()
+            .after(ClientSet::Receive)
+            .after(client_connection_state_system)
+            .run_if(resource_exists::<Client>)
+        )
+        .add_systems(PostUpdate,
+            send_redundant_inputs_system::<E>
+            .before(ClientSet::Send)
+            .run_if(resource_exists::<Client>)
+        )
+    }
+}
+
+fn send_redundant_inputs_system<E: Event + SequencedEvent + Clone>(
+    mut inputs: EventReader<E>,
+    mut unacked: ResMut<UnackedInputs<E>>,
+    mut redundant: EventWriter<RedundantInputs<E>>
+) {
+    // new inputs are already sent by themselves
+    if unacked.len() > 0 {
+        redundant.send(RedundantInputs{
+            inputs: unacked.redundant().cloned().collect()
+        });
+    }
+
+    for input in inputs.read() {
+        unacked.push(input.clone());
+    }
+}
+
+fn reset_unacked_inputs_system<E: Event + SequencedEvent + Clone>(
+    mut started: EventReader<ClientConnectionStarted>,
+    mut unacked: ResMut<UnackedInputs<E>>
+) {
+    if started.read().last().is_some() {
+        unacked.clear();
+    }
+}
+
+fn receive_input_ack_system<E: Event + SequencedEvent + Clone>(
+    query: Query<&NetworkInputAck<E>, (With<OwnerControlling>, Changed<NetworkInputAck<E>>)>,
+    mut unacked: ResMut<UnackedInputs<E>>
+) {
+    for ack in query.iter() {
+        if let Some(sequence) = ack.sequence() {
+            unacked.ack(sequence);
+        }
+    }
+}
+
+fn merge_redundant_inputs_system<E: Event + SequencedEvent + Clone>(
+    mut queue: ResMut<ClientEventQueue<E>>,
+    mut redundant: EventReader<FromClient<RedundantInputs<E>>>,
+    mut received_inputs: ResMut<ReceivedInputs<E>>
+) {
+    let mut received = HashMap::<ClientId, Vec<E>>::new();
+    for (client_id, event) in queue.take() {
+        received.entry(client_id).or_default().push(event);
+    }
+    for FromClient { client_id, event } in redundant.read() {
+        // ignore more than configured, client can not have such many
+        let skip = event.inputs.len().saturating_sub(received_inputs.redundancy);
+        received.entry(*client_id).or_default()
+        .extend(event.inputs.iter().skip(skip).cloned());
+    }
+
+    for (client_id, mut events) in received {
+        events.sort_by(|a, b| a.sequence().cmp_wrapping(&b.sequence()));
+        events.dedup_by_key(|e| e.sequence());
+
+        let latest = received_inputs.get(&client_id);
+        for event in events {
+            let sequence = event.sequence();
+            if latest.is_some_and(|l| !sequence.is_newer_than(&l)) {
+                continue;
+            }
+
+            received_inputs.sequences.insert(client_id, sequence);
+            queue.push(client_id, event);
+        }
+    }
+}
+
+fn queue_accepted_inputs_system<E: Event + Clone>(
+    mut accepted: EventReader<AcceptedFromClient<E>>,
+    mut inputs: ResMut<ServerInputQueue<E>>
+) {
+    for AcceptedFromClient { client_id, event } in accepted.read() {
+        inputs.push(*client_id, event.clone());
+    }
+}
+
+fn received_inputs_disconnect_system<E: Event>(
+    mut server_events: EventReader<ServerEvent>,
+    mut received_inputs: ResMut<ReceivedInputs<E>>,
+    mut inputs: ResMut<ServerInputQueue<E>>
+) {
+    for e in server_events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            received_inputs.sequences.remove(client_id);
+            inputs.inputs.remove(client_id);
+        }
+    }
+}
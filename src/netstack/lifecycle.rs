@@ -0,0 +1,293 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        Arc
+    },
+    time::{Duration, Instant}
+};
+use bevy::{app::AppExit, prelude::*};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{ClientId as RenetClientId, RenetServer};
+use serde::{Deserialize, Serialize};
+use super::{
+    error::NetstackError,
+    notification::{NotificationAppExt, NotificationSet, Notify, Recipients},
+    recording::SessionRecorder,
+    server::{handle_server_event_system, Server}
+};
+
+pub const SHUTDOWN_REASON: &str = "server shutting down";
+// liveness fails when the main loop has not run for this long
+const HEALTH_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const HEALTH_STARTING: u8 = 0;
+const HEALTH_READY: u8 = 1;
+const HEALTH_SHUTTING_DOWN: u8 = 2;
+
+// shared with probe endpoint
+#[derive(Resource, Clone)]
+pub struct HealthHandle(Arc<HealthState>);
+
+struct HealthState {
+    start: Instant,
+    status: AtomicU8,
+    heartbeat_ms: AtomicU64
+}
+
+impl Default for HealthHandle {
+    fn default() -> Self {
+        Self(Arc::new(HealthState{
+            start: Instant::now(),
+            status: AtomicU8::new(HEALTH_STARTING),
+            heartbeat_ms: AtomicU64::new(0)
+        }))
+    }
+}
+
+impl HealthHandle {
+    // main loop is running
+    pub fn is_alive(&self) -> bool {
+        let now = self.0.start.elapsed().as_millis() as u64;
+        let heartbeat = self.0.heartbeat_ms.load(Ordering::Relaxed);
+        now.saturating_sub(heartbeat) < HEALTH_HEARTBEAT_TIMEOUT.as_millis() as u64
+    }
+
+    // server is listening and not shutting down
+    #[inline]
+    pub fn is_ready(&self) -> bool {
+        self.0.status.load(Ordering::Relaxed) == HEALTH_READY
+    }
+
+    #[inline]
+    pub fn is_shutting_down(&self) -> bool {
+        self.0.status.load(Ordering::Relaxed) == HEALTH_SHUTTING_DOWN
+    }
+
+    #[inline]
+    fn beat(&self) {
+        let now = self.0.start.elapsed().as_millis() as u64;
+        self.0.heartbeat_ms.store(now, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn set_status(&self, status: u8) {
+        self.0.status.store(status, Ordering::Relaxed);
+    }
+}
+
+// set from signal handler or any thread
+#[derive(Resource, Clone, Default)]
+pub struct ShutdownHandle(Arc<AtomicBool>);
+
+impl ShutdownHandle {
+    #[inline]
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct ShutdownConfig {
+    // SIGINT and SIGTERM request shutdown, only one app in a process can do this
+    pub handle_signals: bool,
+    // time for the notice to reach clients before disconnecting
+    pub notice_seconds: f32,
+    // time for transport to send disconnect packets before exit
+    pub disconnect_seconds: f32
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self{
+            handle_signals: false,
+            notice_seconds: 0.2,
+            disconnect_seconds: 0.2
+        }
+    }
+}
+
+// sent to a client right before server disconnects it,
+// renet can not carry a reason by itself
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct DisconnectNotice {
+    pub reason: String,
+    // refused on connect rather than dropped later
+    pub rejected: bool
+}
+
+// server side, notifies the client and disconnects it once the notice had time to arrive
+#[derive(Event, Clone, Debug)]
+pub struct DisconnectClient {
+    pub client_id: ClientId,
+    pub notice: DisconnectNotice
+}
+
+// clients notified with DisconnectClient and the time to disconnect them at
+#[derive(Resource, Default)]
+struct PendingDisconnects(Vec<(ClientId, f32)>);
+
+#[derive(Resource, Clone, Copy, PartialEq, Debug)]
+enum ShutdownPhase {
+    Notifying { until: f32 },
+    Disconnecting { until: f32 },
+    Done
+}
+
+pub struct LifecyclePlugin;
+
+impl Plugin for LifecyclePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShutdownConfig>()
+        .init_resource::<ShutdownHandle>()
+        .init_resource::<HealthHandle>()
+        .init_resource::<PendingDisconnects>()
+        .add_event::<DisconnectClient>()
+        .add_notification::<DisconnectNotice>(ChannelKind::Ordered)
+        .add_systems(Startup, setup_signal_handler)
+        .add_systems(Update, (
+            readiness_system,
+            shutdown_system,
+            // new players are not spawned while shutting down
+            reject_while_shutting_down_system.before(handle_server_event_system)
+        ).chain().run_if(resource_exists::<Server>))
+        .add_systems(PostUpdate,
+            disconnect_client_system
+            .before(NotificationSet)
+            .run_if(resource_exists::<Server>)
+        )
+        .add_systems(Last, heartbeat_system);
+    }
+}
+
+fn setup_signal_handler(
+    config: Res<ShutdownConfig>,
+    shutdown: Res<ShutdownHandle>,
+    mut errors: EventWriter<NetstackError>
+) {
+    if !config.handle_signals {
+        return;
+    }
+
+    let shutdown = shutdown.clone();
+    if let Err(e) = ctrlc::set_handler(move || shutdown.request()) {
+        errors.send(NetstackError(e.into()));
+    }
+}
+
+fn heartbeat_system(health: Res<HealthHandle>) {
+    health.beat();
+}
+
+fn readiness_system(health: Res<HealthHandle>) {
+    if !health.is_ready() && !health.is_shutting_down() {
+        health.set_status(HEALTH_READY);
+    }
+}
+
+fn shutdown_system(
+    mut commands: Commands,
+    shutdown: Res<ShutdownHandle>,
+    config: Res<ShutdownConfig>,
+    health: Res<HealthHandle>,
+    phase: Option<Res<ShutdownPhase>>,
+    mut renet_server: ResMut<RenetServer>,
+    mut notices: EventWriter<Notify<DisconnectNotice>>,
+    mut recorder: Option<ResMut<SessionRecorder>>,
+    mut exit: EventWriter<AppExit>,
+    mut errors: EventWriter<NetstackError>,
+    time: Res<Time<Real>>
+) {
+    let now = time.elapsed_seconds();
+    let next = match phase.as_deref() {
+        None => {
+            if !shutdown.is_requested() {
+                return;
+            }
+
+            info!("shutting down, notifying {} clients", renet_server.connected_clients());
+            health.set_status(HEALTH_SHUTTING_DOWN);
+            notices.send(Notify::new(
+                Recipients::All,
+                DisconnectNotice{ reason: SHUTDOWN_REASON.to_string(), rejected: false }
+            ));
+            ShutdownPhase::Notifying{ until: now + config.notice_seconds }
+        }
+        Some(ShutdownPhase::Notifying { until }) => {
+            if now < *until {
+                return;
+            }
+
+            renet_server.disconnect_all();
+            if let Some(recorder) = recorder.as_mut() {
+                if let Err(e) = recorder.stop() {
+                    errors.send(NetstackError(e));
+                }
+            }
+            ShutdownPhase::Disconnecting{ until: now + config.disconnect_seconds }
+        }
+        Some(ShutdownPhase::Disconnecting { until }) => {
+            if now < *until {
+                return;
+            }
+
+            info!("server is shut down");
+            exit.send(AppExit);
+            ShutdownPhase::Done
+        }
+        Some(ShutdownPhase::Done) => return
+    };
+    commands.insert_resource(next);
+}
+
+fn reject_while_shutting_down_system(
+    health: Res<HealthHandle>,
+    mut server_events: EventReader<ServerEvent>,
+    mut disconnects: EventWriter<DisconnectClient>
+) {
+    if !health.is_shutting_down() {
+        server_events.clear();
+        return;
+    }
+
+    for e in server_events.read() {
+        if let ServerEvent::ClientConnected { client_id } = e {
+            disconnects.send(DisconnectClient{
+                client_id: *client_id,
+                notice: DisconnectNotice{ reason: SHUTDOWN_REASON.to_string(), rejected: true }
+            });
+        }
+    }
+}
+
+fn disconnect_client_system(
+    config: Res<ShutdownConfig>,
+    mut requests: EventReader<DisconnectClient>,
+    mut pending: ResMut<PendingDisconnects>,
+    mut renet_server: ResMut<RenetServer>,
+    mut notices: EventWriter<Notify<DisconnectNotice>>,
+    time: Res<Time<Real>>
+) {
+    let now = time.elapsed_seconds();
+    for DisconnectClient { client_id, notice } in requests.read() {
+        // e.g. kicked again by rate limit while waiting
+        if pending.0.iter().any(|(c, _)| c == client_id) {
+            continue;
+        }
+        notices.send(Notify::new(Recipients::One(*client_id), notice.clone()));
+        pending.0.push((*client_id, now + config.notice_seconds));
+    }
+
+    // renet drops messages to a client as soon as it is disconnected
+    pending.0.retain(|(client_id, until)| {
+        if now < *until {
+            return true;
+        }
+        renet_server.disconnect(RenetClientId::from_raw(client_id.get()));
+        false
+    });
+}
@@ -0,0 +1,274 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration
+};
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::prelude::*;
+use bevy_replicon_renet::renet::{ClientId as RenetClientId, RenetClient, RenetServer};
+use super::conditioner::{LinkConditioner, LinkConditionsHandle};
+
+#[derive(Default)]
+struct LoopbackHub {
+    connect_requests: Vec<(u64, [u8; 256])>,
+    accepted: HashSet<u64>,
+    // disconnected by client
+    client_disconnects: Vec<u64>,
+    // disconnected by server
+    server_disconnects: HashSet<u64>,
+    to_server: LinkConditioner<(u64, Vec<u8>)>,
+    to_clients: HashMap<u64, LinkConditioner<Vec<u8>>>,
+    conditions: LinkConditionsHandle,
+    seed: Option<u64>,
+    // every conditioner runs on this single clock,
+    // it follows the server app so that apps with their own time do not mix
+    clock: Duration
+}
+
+// in-process network shared by one server and any number of clients,
+// each of them can live in its own app
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork(Arc<Mutex<LoopbackHub>>);
+
+impl LoopbackNetwork {
+    // conditioners draw random numbers from seeded generators
+    // so that loss and jitter are reproducible
+    pub fn with_seed(seed: u64) -> Self {
+        let network = Self::default();
+        {
+            let mut hub = network.lock();
+            hub.to_server = LinkConditioner::with_seed(seed);
+            hub.seed = Some(seed);
+        }
+        network
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<LoopbackHub> {
+        // a panicked peer should not stop others
+        match self.0.lock() {
+            Ok(hub) => hub,
+            Err(poisoned) => poisoned.into_inner()
+        }
+    }
+
+    // conditions applied to every packet, pass through by default
+    #[inline]
+    pub fn link_conditions(&self) -> LinkConditionsHandle {
+        self.lock().conditions.clone()
+    }
+
+    #[inline]
+    pub fn use_link_conditions(&self, conditions: LinkConditionsHandle) {
+        self.lock().conditions = conditions;
+    }
+
+    fn connect(&self, client_id: u64, user_data: [u8; 256]) {
+        let mut hub = self.lock();
+        hub.server_disconnects.remove(&client_id);
+        let conditioner = match hub.seed {
+            Some(seed) => LinkConditioner::with_seed(seed.wrapping_add(client_id)),
+            None => default()
+        };
+        hub.to_clients.insert(client_id, conditioner);
+        hub.connect_requests.push((client_id, user_data));
+    }
+}
+
+#[derive(Resource)]
+pub struct LoopbackServerTransport {
+    network: LoopbackNetwork,
+    user_data: HashMap<u64, [u8; 256]>
+}
+
+impl LoopbackServerTransport {
+    #[inline]
+    pub fn new(network: LoopbackNetwork) -> Self {
+        Self{
+            network,
+            user_data: default()
+        }
+    }
+
+    #[inline]
+    pub fn user_data(&self, client_id: u64) -> Option<[u8; 256]> {
+        self.user_data.get(&client_id).copied()
+    }
+}
+
+#[derive(Resource)]
+pub struct LoopbackClientTransport {
+    network: LoopbackNetwork,
+    client_id: u64,
+    disconnected: bool
+}
+
+impl LoopbackClientTransport {
+    pub fn new(network: LoopbackNetwork, client_id: u64, user_data: [u8; 256]) -> Self {
+        network.connect(client_id, user_data);
+        Self{
+            network,
+            client_id,
+            disconnected: false
+        }
+    }
+}
+
+pub struct LoopbackServerPlugin;
+
+impl Plugin for LoopbackServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate,
+            loopback_server_receive_system
+            .before(ServerSet::ReceivePackets)
+            .run_if(resource_exists::<LoopbackServerTransport>)
+        )
+        .add_systems(PostUpdate,
+            loopback_server_send_system
+            .after(ServerSet::SendPackets)
+            .run_if(resource_exists::<LoopbackServerTransport>)
+        );
+    }
+}
+
+pub struct LoopbackClientPlugin;
+
+impl Plugin for LoopbackClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate,
+            loopback_client_receive_system
+            .before(ClientSet::ReceivePackets)
+            .run_if(resource_exists::<LoopbackClientTransport>)
+        )
+        .add_systems(PostUpdate,
+            loopback_client_send_system
+            .after(ClientSet::SendPackets)
+            .run_if(resource_exists::<LoopbackClientTransport>)
+        );
+    }
+}
+
+fn loopback_server_receive_system(
+    mut transport: ResMut<LoopbackServerTransport>,
+    mut renet_server: ResMut<RenetServer>,
+    time: Res<Time<Real>>
+) {
+    let network = transport.network.clone();
+    let mut hub = network.lock();
+    hub.clock = hub.clock.max(time.elapsed());
+    let now = hub.clock;
+
+    for (client_id, user_data) in std::mem::take(&mut hub.connect_requests) {
+        renet_server.add_connection(RenetClientId::from_raw(client_id));
+        transport.user_data.insert(client_id, user_data);
+        hub.accepted.insert(client_id);
+    }
+
+    for client_id in std::mem::take(&mut hub.client_disconnects) {
+        renet_server.remove_connection(RenetClientId::from_raw(client_id));
+        transport.user_data.remove(&client_id);
+        hub.accepted.remove(&client_id);
+    }
+
+    // disconnect requested by server side such as kick
+    for renet_client_id in renet_server.disconnections_id() {
+        renet_server.remove_connection(renet_client_id);
+        let client_id = renet_client_id.raw();
+        transport.user_data.remove(&client_id);
+        hub.accepted.remove(&client_id);
+        hub.to_clients.remove(&client_id);
+        hub.server_disconnects.insert(client_id);
+    }
+
+    for (client_id, packet) in hub.to_server.receive(now) {
+        if !hub.accepted.contains(&client_id) {
+            continue;
+        }
+        if let Err(e) = renet_server.process_packet_from(
+            &packet,
+            RenetClientId::from_raw(client_id)
+        ) {
+            warn!("loopback packet from client: {client_id} is discarded: {e}");
+        }
+    }
+}
+
+fn loopback_server_send_system(
+    transport: Res<LoopbackServerTransport>,
+    mut renet_server: ResMut<RenetServer>
+) {
+    let mut hub = transport.network.lock();
+    let now = hub.clock;
+    let downstream = hub.conditions.get().downstream;
+    for renet_client_id in renet_server.clients_id() {
+        let packets = match renet_server.get_packets_to_send(renet_client_id) {
+            Ok(p) => p,
+            Err(_) => continue
+        };
+
+        if let Some(conditioner) = hub.to_clients.get_mut(&renet_client_id.raw()) {
+            for packet in packets {
+                conditioner.send(&downstream, now, packet);
+            }
+        }
+    }
+}
+
+fn loopback_client_receive_system(
+    mut transport: ResMut<LoopbackClientTransport>,
+    mut renet_client: ResMut<RenetClient>
+) {
+    if transport.disconnected {
+        return;
+    }
+
+    let network = transport.network.clone();
+    let mut hub = network.lock();
+    if hub.server_disconnects.remove(&transport.client_id) {
+        renet_client.disconnect_due_to_transport();
+        transport.disconnected = true;
+        return;
+    }
+
+    if !hub.accepted.contains(&transport.client_id) {
+        return;
+    }
+    if renet_client.is_connecting() {
+        renet_client.set_connected();
+    }
+
+    let now = hub.clock;
+    if let Some(conditioner) = hub.to_clients.get_mut(&transport.client_id) {
+        for packet in conditioner.receive(now) {
+            renet_client.process_packet(&packet);
+        }
+    }
+}
+
+fn loopback_client_send_system(
+    mut transport: ResMut<LoopbackClientTransport>,
+    mut renet_client: ResMut<RenetClient>
+) {
+    if transport.disconnected {
+        return;
+    }
+
+    let network = transport.network.clone();
+    let mut hub = network.lock();
+    if renet_client.is_disconnected() {
+        hub.client_disconnects.push(transport.client_id);
+        hub.to_clients.remove(&transport.client_id);
+        transport.disconnected = true;
+        return;
+    }
+
+    if !hub.accepted.contains(&transport.client_id) {
+        return;
+    }
+
+    let client_id = transport.client_id;
+    let now = hub.clock;
+    let upstream = hub.conditions.get().upstream;
+    for packet in renet_client.get_packets_to_send() {
+        hub.to_server.send(&upstream, now, (client_id, packet));
+    }
+}
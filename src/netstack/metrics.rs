@@ -0,0 +1,376 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+use bevy::{prelude::*, utils::{get_short_name, HashSet}};
+use bevy_replicon::prelude::*;
+use bevy_replicon_snap::prelude::*;
+use super::{
+    error::NetstackError,
+    lifecycle::HealthHandle,
+    input::ServerInputQueue,
+    server::Server,
+    stats::NetworkStats
+};
+
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Resource, Clone)]
+pub struct MetricsConfig {
+    // keep on localhost, there is no authentication
+    pub listen_addr: SocketAddr,
+    pub update_interval_seconds: f32
+}
+
+// rendered text shared with http thread
+#[derive(Resource, Clone, Default)]
+pub struct MetricsHandle(Arc<Mutex<String>>);
+
+impl MetricsHandle {
+    #[inline]
+    pub fn get(&self) -> String {
+        match self.0.lock() {
+            Ok(t) => t.clone(),
+            Err(poisoned) => poisoned.into_inner().clone()
+        }
+    }
+
+    #[inline]
+    fn set(&self, text: String) {
+        match self.0.lock() {
+            Ok(mut t) => *t = text,
+            Err(poisoned) => *poisoned.into_inner() = text
+        }
+    }
+}
+
+struct Gauge {
+    help: &'static str,
+    // labels to value
+    values: BTreeMap<String, f64>
+}
+
+// counters and gauges collected by netstack and game systems
+#[derive(Resource, Default)]
+pub struct NetstackMetrics {
+    kicks: u64,
+    errors: u64,
+    tick_duration_seconds: f64,
+    gauges: BTreeMap<&'static str, Gauge>
+}
+
+impl NetstackMetrics {
+    #[inline]
+    pub fn kicks(&self) -> u64 {
+        self.kicks
+    }
+
+    #[inline]
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    // wall time of the latest simulation step
+    #[inline]
+    pub fn tick_duration_seconds(&self) -> f64 {
+        self.tick_duration_seconds
+    }
+
+    #[inline]
+    pub fn count_kick(&mut self) {
+        self.kicks += 1;
+    }
+
+    // labels are like `client_id="1"`
+    pub fn set_gauge(&mut self, name: &'static str, help: &'static str, labels: String, value: f64) {
+        self.gauges.entry(name)
+        .or_insert_with(|| Gauge{
+            help,
+            values: default()
+        })
+        .values.insert(labels, value);
+    }
+
+    pub fn remove_gauge(&mut self, name: &'static str, labels: &str) {
+        if let Some(g) = self.gauges.get_mut(name) {
+            g.values.remove(labels);
+        }
+    }
+
+    fn render(&self, stats: &NetworkStats) -> String {
+        let mut text = String::new();
+        write_metric(&mut text, "netstack_connected_clients", "gauge",
+            "connected clients",
+            [(String::new(), stats.len() as f64)]
+        );
+        write_metric(&mut text, "netstack_client_rtt_seconds", "gauge",
+            "round trip time of each client",
+            stats.iter().map(|(id, c)| (format!("client_id=\"{}\"", id.get()), c.latest().rtt))
+        );
+        write_metric(&mut text, "netstack_client_packet_loss", "gauge",
+            "packet loss ratio of each client",
+            stats.iter().map(|(id, c)| (format!("client_id=\"{}\"", id.get()), c.latest().packet_loss))
+        );
+        write_metric(&mut text, "netstack_tick_duration_seconds", "gauge",
+            "wall time of the latest simulation step",
+            [(String::new(), self.tick_duration_seconds)]
+        );
+        write_metric(&mut text, "netstack_kicks_total", "counter",
+            "clients disconnected by server",
+            [(String::new(), self.kicks as f64)]
+        );
+        write_metric(&mut text, "netstack_errors_total", "counter",
+            "netstack errors",
+            [(String::new(), self.errors as f64)]
+        );
+        for (name, gauge) in self.gauges.iter() {
+            write_metric(&mut text, name, "gauge", gauge.help,
+                gauge.values.iter().map(|(l, v)| (l.clone(), *v))
+            );
+        }
+        text
+    }
+}
+
+fn write_metric(
+    text: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    values: impl IntoIterator<Item = (String, f64)>
+) {
+    let _ = writeln!(text, "# HELP {name} {help}");
+    let _ = writeln!(text, "# TYPE {name} {kind}");
+    for (labels, value) in values {
+        if labels.is_empty() {
+            let _ = writeln!(text, "{name} {value}");
+        } else {
+            let _ = writeln!(text, "{name}{{{labels}}} {value}");
+        }
+    }
+}
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetstackMetrics>()
+        .add_systems(Startup,
+            setup_metrics.run_if(resource_exists::<MetricsConfig>)
+        )
+        .add_systems(FixedFirst, begin_tick_system.run_if(resource_exists::<Server>))
+        .add_systems(FixedLast, end_tick_system.run_if(resource_exists::<Server>))
+        .add_systems(Last, (
+            count_errors_system,
+            render_metrics_system.run_if(resource_exists::<MetricsHandle>)
+        ).chain().run_if(resource_exists::<Server>));
+    }
+}
+
+pub trait MetricsAppExt {
+    // largest and mean buffer length among entities
+    fn export_snapshot_buffer<C>(&mut self) -> &mut Self
+    where C: Component + Interpolate + Clone;
+
+    // inputs of each client waiting in ServerInputQueue<E> before a simulation step
+    fn export_input_queue<E>(&mut self) -> &mut Self
+    where E: Event;
+}
+
+impl MetricsAppExt for App {
+    fn export_snapshot_buffer<C>(&mut self) -> &mut Self
+    where C: Component + Interpolate + Clone {
+        self.add_systems(Last,
+            snapshot_buffer_metrics_system::<C>
+            .before(render_metrics_system)
+            .run_if(resource_exists::<Server>.and_then(resource_exists::<MetricsHandle>))
+        )
+    }
+
+    fn export_input_queue<E>(&mut self) -> &mut Self
+    where E: Event {
+        self.add_systems(FixedPreUpdate,
+            input_queue_metrics_system::<E>
+            .run_if(resource_exists::<Server>.and_then(resource_exists::<ServerInputQueue<E>>))
+        )
+    }
+}
+
+#[derive(Resource)]
+struct TickStart(Instant);
+
+fn setup_metrics(
+    mut commands: Commands, 
+    config: Res<MetricsConfig>,
+    health: Res<HealthHandle>,
+    mut errors: EventWriter<NetstackError>
+) {
+    let handle = MetricsHandle::default();
+    match spawn_metrics_server(config.listen_addr, handle.clone(), health.clone()) {
+        Ok(_) => {
+            info!(
+                "metrics are served at http://{0}/metrics, probes at /healthz and /readyz", 
+                config.listen_addr
+            );
+            commands.insert_resource(handle);
+        }
+        Err(e) => {
+            errors.send(NetstackError(e));
+        }
+    }
+}
+
+fn spawn_metrics_server(
+    listen_addr: SocketAddr,
+    handle: MetricsHandle,
+    health: HealthHandle
+) -> anyhow::Result<thread::JoinHandle<()>> {
+    let listener = TcpListener::bind(listen_addr)?;
+    let thread = thread::Builder::new()
+    .name("metrics".to_string())
+    .spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(s) => {
+                    if let Err(e) = respond(s, &handle, &health) {
+                        debug!("metrics request failed: {e}");
+                    }
+                }
+                Err(e) => {
+                    warn!("metrics listener: {e}");
+                }
+            }
+        }
+    })?;
+    Ok(thread)
+}
+
+fn respond(
+    mut stream: TcpStream, 
+    handle: &MetricsHandle,
+    health: &HealthHandle
+) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(METRICS_READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    // "GET /metrics HTTP/1.1"
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next(), parts.next());
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", handle.get()),
+        (Some("GET"), Some("/healthz")) => probe(health.is_alive()),
+        (Some("GET"), Some("/readyz")) => probe(health.is_ready()),
+        _ => ("404 Not Found", String::new())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {METRICS_CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[inline]
+fn probe(ok: bool) -> (&'static str, String) {
+    if ok {
+        ("200 OK", "ok\n".to_string())
+    } else {
+        ("503 Service Unavailable", "unavailable\n".to_string())
+    }
+}
+
+fn begin_tick_system(mut commands: Commands) {
+    commands.insert_resource(TickStart(Instant::now()));
+}
+
+fn end_tick_system(
+    start: Option<Res<TickStart>>,
+    mut metrics: ResMut<NetstackMetrics>
+) {
+    if let Some(start) = start {
+        metrics.tick_duration_seconds = start.0.elapsed().as_secs_f64();
+    }
+}
+
+fn count_errors_system(
+    mut errors: EventReader<NetstackError>,
+    mut metrics: ResMut<NetstackMetrics>
+) {
+    metrics.errors += errors.read().count() as u64;
+}
+
+fn render_metrics_system(
+    handle: Res<MetricsHandle>,
+    metrics: Res<NetstackMetrics>,
+    stats: Res<NetworkStats>,
+    config: Option<Res<MetricsConfig>>,
+    mut elapsed: Local<f32>,
+    time: Res<Time<Real>>
+) {
+    let interval = config.map(|c| c.update_interval_seconds).unwrap_or_default();
+    *elapsed += time.delta_seconds();
+    if *elapsed < interval {
+        return;
+    }
+    *elapsed = 0.0;
+
+    handle.set(metrics.render(&stats));
+}
+
+fn snapshot_buffer_metrics_system<C: Component + Interpolate + Clone>(
+    query: Query<&ComponentSnapshotBuffer<C>>,
+    mut metrics: ResMut<NetstackMetrics>
+) {
+    let lengths = query.iter().map(|b| b.iter().count()).collect::<Vec<_>>();
+    let max = lengths.iter().copied().max().unwrap_or_default();
+    let mean = if lengths.is_empty() {
+        0.0
+    } else {
+        lengths.iter().sum::<usize>() as f64 / lengths.len() as f64
+    };
+
+    let component = get_short_name(std::any::type_name::<C>());
+    metrics.set_gauge("netstack_snapshot_buffer_max", "largest snapshot buffer length",
+        format!("component=\"{component}\""), max as f64
+    );
+    metrics.set_gauge("netstack_snapshot_buffer_mean", "mean snapshot buffer length",
+        format!("component=\"{component}\""), mean
+    );
+}
+
+#[inline]
+fn input_queue_labels<E>(client_id: &ClientId) -> String {
+    let event = get_short_name(std::any::type_name::<E>());
+    format!("event=\"{event}\",client_id=\"{}\"", client_id.get())
+}
+
+// sampled before the simulation drains the queue
+fn input_queue_metrics_system<E: Event>(
+    queue: Res<ServerInputQueue<E>>,
+    mut metrics: ResMut<NetstackMetrics>,
+    mut exported: Local<HashSet<ClientId>>
+) {
+    // queues of disconnected clients are removed
+    exported.retain(|client_id| {
+        let connected = queue.iter().any(|(id, _)| id == client_id);
+        if !connected {
+            metrics.remove_gauge("netstack_input_queue_depth", &input_queue_labels::<E>(client_id));
+        }
+        connected
+    });
+    for (client_id, depth) in queue.iter() {
+        metrics.set_gauge("netstack_input_queue_depth", "inputs waiting for the simulation step",
+            input_queue_labels::<E>(client_id), depth as f64
+        );
+        exported.insert(*client_id);
+    }
+}
@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use bevy_replicon::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use super::{components::NetworkPlayer, server::Server};
+
+// server side only, players in the same group are notified together (e.g. team)
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct NotificationGroup(pub u32);
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Recipients {
+    All,
+    AllExcept(ClientId),
+    One(ClientId),
+    Many(Vec<ClientId>),
+    Group(NotificationGroup)
+}
+
+// written by server, client reads E itself
+#[derive(Event, Clone, Debug)]
+pub struct Notify<E: Event> {
+    pub recipients: Recipients,
+    pub event: E
+}
+
+impl<E: Event> Notify<E> {
+    #[inline]
+    pub fn new(recipients: Recipients, event: E) -> Self {
+        Self{
+            recipients,
+            event
+        }
+    }
+}
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct NotificationSet;
+
+pub struct NotificationPlugin;
+
+impl Plugin for NotificationPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(PostUpdate,
+            NotificationSet.before(ServerSet::Send)
+        );
+    }
+}
+
+pub trait NotificationAppExt {
+    // Ordered or Unordered for reliable notifications
+    fn add_notification<E>(&mut self, channel: impl Into<RepliconChannel>) -> &mut Self
+    where E: Event + Serialize + DeserializeOwned + Clone;
+}
+
+impl NotificationAppExt for App {
+    fn add_notification<E>(&mut self, channel: impl Into<RepliconChannel>) -> &mut Self
+    where E: Event + Serialize + DeserializeOwned + Clone {
+        self.add_event::<Notify<E>>()
+        .add_server_event::<E>(channel)
+        .add_systems(PostUpdate,
+            send_notification_system::<E>
+            .in_set(NotificationSet)
+            .run_if(resource_exists::<Server>)
+        )
+    }
+}
+
+fn send_notification_system<E: Event + Clone>(
+    query: Query<(&NetworkPlayer, Option<&NotificationGroup>)>,
+    mut notifications: EventReader<Notify<E>>,
+    mut events: EventWriter<ToClients<E>>
+) {
+    for Notify { recipients, event } in notifications.read() {
+        let mode = match recipients {
+            Recipients::All => SendMode::Broadcast,
+            Recipients::AllExcept(client_id) => SendMode::BroadcastExcept(*client_id),
+            Recipients::One(client_id) => SendMode::Direct(*client_id),
+            Recipients::Many(client_ids) => {
+                for client_id in client_ids {
+                    events.send(ToClients{
+                        mode: SendMode::Direct(*client_id),
+                        event: event.clone()
+                    });
+                }
+                continue;
+            }
+            Recipients::Group(group) => {
+                for (p, _) in query.iter().filter(|(_, g)| *g == Some(group)) {
+                    events.send(ToClients{
+                        mode: SendMode::Direct(p.client_id()),
+                        event: event.clone()
+                    });
+                }
+                continue;
+            }
+        };
+        events.send(ToClients{
+            mode,
+            event: event.clone()
+        });
+    }
+}
@@ -0,0 +1,306 @@
+use std::any::TypeId;
+use bevy::{
+    ecs::{
+        change_detection::MAX_CHANGE_AGE,
+        component::Tick,
+        entity::Entities,
+        system::SystemChangeTick
+    },
+    prelude::*,
+    utils::HashMap
+};
+use bevy_replicon::{
+    core::replicon_tick::RepliconTick,
+    prelude::*,
+    server::connected_clients::ConnectedClients
+};
+use serde::Serialize;
+use super::{
+    components::{NetworkPlayer, NetworkTranslation2D},
+    resources::PlayerEntityMap,
+    server::Server,
+    visibility::VisibilitySet
+};
+
+// entity id and component id in update message, estimated
+const ENTITY_OVERHEAD_BYTES: usize = 4;
+const COMPONENT_OVERHEAD_BYTES: usize = 1;
+
+// tunable from ServerConfig
+#[derive(Resource, Clone, Debug)]
+pub struct ReplicationBudget {
+    // estimated bytes of component updates per client per network tick,
+    // 0 for unlimited
+    pub bytes_per_client: usize,
+    // priority accumulated every network tick while an update is not sent
+    pub owner_priority: f32,
+    pub near_priority: f32,
+    pub far_priority: f32,
+    pub near_radius: f32
+}
+
+impl Default for ReplicationBudget {
+    fn default() -> Self {
+        Self{
+            bytes_per_client: 0,
+            owner_priority: 100.0,
+            near_priority: 10.0,
+            far_priority: 1.0,
+            near_radius: 30.0
+        }
+    }
+}
+
+impl ReplicationBudget {
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.bytes_per_client == 0
+    }
+}
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ReplicationPrioritySet {
+    // collects changed prioritized components
+    Collect,
+    // decides which entities are sent within budget
+    Schedule,
+    // hides deferred changes from replicon for this send
+    Apply,
+    // gives the changes their own ticks back after the send
+    Restore
+}
+
+#[derive(Default)]
+struct PendingUpdate {
+    // serialized size of each changed component
+    components: HashMap<TypeId, usize>,
+    // spawned in this tick, initial state is always sent
+    added: bool
+}
+
+impl PendingUpdate {
+    #[inline]
+    fn bytes(&self) -> usize {
+        ENTITY_OVERHEAD_BYTES + self.components.values()
+        .map(|b| b + COMPONENT_OVERHEAD_BYTES)
+        .sum::<usize>()
+    }
+}
+
+// entities with unsent changes of prioritized components
+#[derive(Resource, Default)]
+pub struct ReplicationScheduler {
+    pending: HashMap<Entity, PendingUpdate>,
+    // accumulated by each client while its update is starved
+    priorities: HashMap<(ClientId, Entity), f32>,
+    released: HashMap<Entity, PendingUpdate>,
+    // change ticks moved only for the send, restored right after it
+    moved: HashMap<(Entity, TypeId), Tick>,
+    deferred: usize
+}
+
+impl ReplicationScheduler {
+    #[inline]
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    // updates deferred for a client in the latest network tick, summed over clients
+    #[inline]
+    pub fn deferred_len(&self) -> usize {
+        self.deferred
+    }
+
+    #[inline]
+    pub fn priority(&self, client_id: &ClientId, entity: &Entity) -> Option<f32> {
+        self.priorities.get(&(*client_id, *entity)).copied()
+    }
+}
+
+pub struct ReplicationPriorityPlugin;
+
+impl Plugin for ReplicationPriorityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationBudget>()
+        .init_resource::<ReplicationScheduler>()
+        .configure_sets(PostUpdate, (
+            ReplicationPrioritySet::Collect,
+            ReplicationPrioritySet::Schedule,
+            ReplicationPrioritySet::Apply
+        ).chain()
+        .after(VisibilitySet::Apply)
+        .before(ServerSet::Send)
+        // budget is per network tick
+        .run_if(resource_exists::<Server>.and_then(resource_changed::<RepliconTick>)))
+        .configure_sets(PostUpdate,
+            ReplicationPrioritySet::Restore
+            .after(ServerSet::Send)
+            .run_if(resource_exists::<Server>)
+        )
+        .add_systems(PostUpdate,
+            schedule_system.in_set(ReplicationPrioritySet::Schedule)
+        );
+    }
+}
+
+pub trait ReplicationPriorityAppExt {
+    // updates of this replicated component are sent by priority within budget
+    fn prioritize_replication<C>(&mut self) -> &mut Self
+    where C: Component + Serialize;
+}
+
+impl ReplicationPriorityAppExt for App {
+    fn prioritize_replication<C>(&mut self) -> &mut Self
+    where C: Component + Serialize {
+        self.add_systems(PostUpdate, (
+            collect_changes_system::<C>.in_set(ReplicationPrioritySet::Collect),
+            defer_changes_system::<C>.in_set(ReplicationPrioritySet::Apply),
+            restore_changes_system::<C>.in_set(ReplicationPrioritySet::Restore)
+        ))
+    }
+}
+
+fn collect_changes_system<C: Component + Serialize>(
+    query: Query<(Entity, Ref<C>), Changed<C>>,
+    mut scheduler: ResMut<ReplicationScheduler>
+) {
+    let type_id = TypeId::of::<C>();
+    for (e, c) in query.iter() {
+        let bytes = bincode::serialized_size(&*c).unwrap_or_default() as usize;
+        let pending = scheduler.pending.entry(e).or_default();
+        pending.components.insert(type_id, bytes);
+        pending.added |= c.is_added();
+    }
+}
+
+// every client orders the updates it sees by its own priority,
+// owner first, then nearby players and far ones,
+// and takes what fits in its budget. replicon keeps change ticks per entity,
+// so an update taken by one client reaches every client seeing the entity
+// and counts against their budgets too.
+// starved updates keep accumulating priority for each client until they are sent
+fn schedule_system(
+    budget: Res<ReplicationBudget>,
+    connected_clients: Res<ConnectedClients>,
+    player_entities: Res<PlayerEntityMap>,
+    players: Query<&NetworkPlayer>,
+    translations: Query<&NetworkTranslation2D>,
+    entities: &Entities,
+    mut scheduler: ResMut<ReplicationScheduler>,
+    mut remaining: Local<HashMap<ClientId, usize>>,
+    mut order: Local<Vec<(ClientId, Entity, f32)>>
+) {
+    let scheduler = scheduler.as_mut();
+    scheduler.released.clear();
+    scheduler.deferred = 0;
+    if budget.is_unlimited() {
+        scheduler.released.extend(scheduler.pending.drain());
+        scheduler.priorities.clear();
+        return;
+    }
+
+    // despawned while pending, or client left
+    scheduler.pending.retain(|e, _| entities.contains(*e));
+    let pending = &scheduler.pending;
+    scheduler.priorities.retain(|(client_id, e), _| {
+        pending.contains_key(e) && connected_clients.iter().any(|c| c.id() == *client_id)
+    });
+
+    order.clear();
+    for client in connected_clients.iter() {
+        let client_id = client.id();
+        let center = player_entities.get(&client_id)
+        .and_then(|e| translations.get(*e).ok())
+        .map(|t| t.0);
+        for (e, pending) in scheduler.pending.iter() {
+            if !client.visibility().is_visible(*e) {
+                continue;
+            }
+            let owner = players.get(*e).ok().map(|p| p.client_id());
+            let position = translations.get(*e).ok().map(|t| t.0);
+            let client_priority = if owner == Some(client_id) {
+                budget.owner_priority
+            } else {
+                match (position, center) {
+                    (Some(p), Some(c)) if p.distance(c) <= budget.near_radius => budget.near_priority,
+                    _ => budget.far_priority
+                }
+            };
+            let priority = scheduler.priorities.entry((client_id, *e)).or_default();
+            *priority += client_priority;
+            let priority = if pending.added { f32::INFINITY } else { *priority };
+            order.push((client_id, *e, priority));
+        }
+    }
+    order.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    remaining.clear();
+    remaining.extend(connected_clients.iter().map(|c| (c.id(), budget.bytes_per_client)));
+    for (client_id, e, _) in order.iter() {
+        if scheduler.released.contains_key(e) {
+            continue;
+        }
+        let Some(pending) = scheduler.pending.get(e) else {
+            continue;
+        };
+        let bytes = pending.bytes();
+        // an update larger than the budget goes alone
+        let left = remaining.get(client_id).copied().unwrap_or_default();
+        if !pending.added && left < bytes && left != budget.bytes_per_client {
+            scheduler.deferred += 1;
+            continue;
+        }
+
+        for client in connected_clients.iter().filter(|c| c.visibility().is_visible(*e)) {
+            if let Some(left) = remaining.get_mut(&client.id()) {
+                *left = left.saturating_sub(bytes);
+            }
+        }
+        if let Some(pending) = scheduler.pending.remove(e) {
+            scheduler.released.insert(*e, pending);
+        }
+    }
+    let released = &scheduler.released;
+    scheduler.priorities.retain(|(_, e), _| !released.contains_key(e));
+}
+
+// replicon sends what changed since the client acknowledged the entity,
+// deferred changes get the oldest tick and released ones the current tick.
+// moved only for this send, other readers of the change ticks never see it
+fn defer_changes_system<C: Component>(
+    mut query: Query<&mut C>,
+    mut scheduler: ResMut<ReplicationScheduler>,
+    system_ticks: SystemChangeTick
+) {
+    let type_id = TypeId::of::<C>();
+    let oldest = Tick::new(system_ticks.this_run().get().wrapping_sub(MAX_CHANGE_AGE));
+    let scheduler = scheduler.as_mut();
+    let updates = scheduler.released.iter()
+    .map(|(e, update)| (e, update, system_ticks.this_run()))
+    .chain(scheduler.pending.iter().map(|(e, update)| (e, update, oldest)));
+    for (e, update, tick) in updates {
+        if !update.components.contains_key(&type_id) {
+            continue;
+        }
+        if let Ok(mut c) = query.get_mut(*e) {
+            scheduler.moved.insert((*e, type_id), c.last_changed());
+            c.set_last_changed(tick);
+        }
+    }
+}
+
+fn restore_changes_system<C: Component>(
+    mut query: Query<&mut C>,
+    mut scheduler: ResMut<ReplicationScheduler>
+) {
+    let type_id = TypeId::of::<C>();
+    scheduler.moved.retain(|(e, t), tick| {
+        if *t != type_id {
+            return true;
+        }
+        if let Ok(mut c) = query.get_mut(*e) {
+            c.set_last_changed(*tick);
+        }
+        false
+    });
+}
@@ -0,0 +1,211 @@
+use std::marker::PhantomData;
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::prelude::*;
+use super::{
+    client_event::{ClientEventAppExt, ClientEventQueue},
+    lifecycle::{DisconnectClient, DisconnectNotice},
+    metrics::NetstackMetrics,
+    server::{Server, ServerNetstackSet}
+};
+
+pub const RATE_LIMIT_KICK_REASON: &str = "too many messages";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RateLimitPolicy {
+    // discard events over the limit
+    Drop,
+    // discard events over the limit and disconnect the client
+    Kick
+}
+
+// token bucket, burst of capacity events then refill_per_second events
+#[derive(Clone, Copy)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub refill_per_second: f32,
+    pub policy: RateLimitPolicy
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RateLimitCounters {
+    pub accepted: u64,
+    pub dropped: u64,
+    pub kicked: u64
+}
+
+struct TokenBucket {
+    tokens: f32,
+    last_refill: f32
+}
+
+impl TokenBucket {
+    #[inline]
+    fn full(limit: &RateLimit, now: f32) -> Self {
+        Self{
+            tokens: limit.capacity as f32,
+            last_refill: now
+        }
+    }
+
+    fn try_consume(&mut self, limit: &RateLimit, now: f32) -> bool {
+        let elapsed = (now - self.last_refill).max(0.0);
+        self.tokens = (self.tokens + elapsed * limit.refill_per_second)
+        .min(limit.capacity as f32);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// per client limiter for one client event type
+#[derive(Resource)]
+pub struct ClientEventRateLimiter<E: Event> {
+    limit: RateLimit,
+    buckets: HashMap<ClientId, TokenBucket>,
+    client_counters: HashMap<ClientId, RateLimitCounters>,
+    // waiting for disconnect after the kick notice
+    kicked: HashSet<ClientId>,
+    counters: RateLimitCounters,
+    phantom: PhantomData<E>
+}
+
+impl<E: Event> ClientEventRateLimiter<E> {
+    #[inline]
+    pub fn new(limit: RateLimit) -> Self {
+        Self{
+            limit,
+            buckets: default(),
+            client_counters: default(),
+            kicked: default(),
+            counters: default(),
+            phantom: PhantomData
+        }
+    }
+
+    #[inline]
+    pub fn limit(&self) -> &RateLimit {
+        &self.limit
+    }
+
+    // totals since server started, including disconnected clients
+    #[inline]
+    pub fn counters(&self) -> &RateLimitCounters {
+        &self.counters
+    }
+
+    // kept after the client disconnects
+    #[inline]
+    pub fn client_counters(&self, client_id: &ClientId) -> Option<&RateLimitCounters> {
+        self.client_counters.get(client_id)
+    }
+
+    fn try_consume(&mut self, client_id: ClientId, now: f32) -> bool {
+        let limit = self.limit;
+        let ok = self.buckets.entry(client_id)
+        .or_insert_with(|| TokenBucket::full(&limit, now))
+        .try_consume(&limit, now);
+
+        let client_counters = self.client_counters.entry(client_id).or_default();
+        if ok {
+            client_counters.accepted += 1;
+            self.counters.accepted += 1;
+        } else {
+            client_counters.dropped += 1;
+            self.counters.dropped += 1;
+        }
+        ok
+    }
+
+    fn kick(&mut self, client_id: ClientId) {
+        self.client_counters.entry(client_id).or_default().kicked += 1;
+        self.counters.kicked += 1;
+        self.kicked.insert(client_id);
+    }
+
+    #[inline]
+    fn is_kicked(&self, client_id: &ClientId) -> bool {
+        self.kicked.contains(client_id)
+    }
+
+    // counters stay for monitoring, kicked clients are the ones worth seeing
+    fn remove_client(&mut self, client_id: &ClientId) {
+        self.buckets.remove(client_id);
+        self.kicked.remove(client_id);
+    }
+}
+
+pub trait ClientEventRateLimitAppExt {
+    // event should be registered with add_client_event or use_client_event_snapshots,
+    // gameplay reads AcceptedFromClient<E>
+    fn rate_limit_client_event<E: Event + Clone>(&mut self, limit: RateLimit) -> &mut Self;
+}
+
+impl ClientEventRateLimitAppExt for App {
+    fn rate_limit_client_event<E: Event + Clone>(&mut self, limit: RateLimit) -> &mut Self {
+        self.accept_client_event::<E>()
+        .insert_resource(ClientEventRateLimiter::<E>::new(limit))
+        .add_systems(PreUpdate, (
+            rate_limit_client_event_system::<E>,
+            rate_limiter_disconnect_system::<E>
+        )
+            .chain()
+            .in_set(ServerNetstackSet::ClientEventFilter)
+            .run_if(resource_exists::<Server>)
+        )
+    }
+}
+
+fn rate_limit_client_event_system<E: Event>(
+    mut queue: ResMut<ClientEventQueue<E>>,
+    mut limiter: ResMut<ClientEventRateLimiter<E>>,
+    mut metrics: ResMut<NetstackMetrics>,
+    mut disconnects: EventWriter<DisconnectClient>,
+    time: Res<Time>
+) {
+    let received = queue.take();
+    let now = time.elapsed_seconds();
+    for (client_id, event) in received {
+        if limiter.is_kicked(&client_id) {
+            continue;
+        }
+
+        if limiter.try_consume(client_id, now) {
+            queue.push(client_id, event);
+            continue;
+        }
+
+        match limiter.limit().policy {
+            RateLimitPolicy::Drop => {
+                debug!("client: {client_id:?} exceeded rate limit, dropping event");
+            }
+            RateLimitPolicy::Kick => {
+                warn!("client: {client_id:?} exceeded rate limit, kicking...");
+                disconnects.send(DisconnectClient{
+                    client_id,
+                    notice: DisconnectNotice{
+                        reason: RATE_LIMIT_KICK_REASON.to_string(),
+                        rejected: false
+                    }
+                });
+                limiter.kick(client_id);
+                metrics.count_kick();
+            }
+        }
+    }
+}
+
+fn rate_limiter_disconnect_system<E: Event>(
+    mut server_events: EventReader<ServerEvent>,
+    mut limiter: ResMut<ClientEventRateLimiter<E>>
+) {
+    for e in server_events.read() {
+        if let ServerEvent::ClientDisconnected { client_id, .. } = e {
+            limiter.remove_client(client_id);
+        }
+    }
+}
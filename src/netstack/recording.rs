@@ -0,0 +1,549 @@
+use std::{
+    any::type_name,
+    fs::{self, File},
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    time::SystemTime
+};
+use bevy::prelude::*;
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use anyhow::{anyhow, bail};
+use super::{
+    client_event::{AcceptedFromClient, ClientEventAppExt},
+    components::{NetworkPlayer, ServerNetworkPlayerInfo},
+    error::NetstackError,
+    server::{Server, ServerNetstackSet},
+    tick::{increment_replicon_tick_system, SimulationTickConfig}
+};
+
+pub const REPLAY_MAGIC: [u8; 4] = *b"BNDR";
+// bump when any recorded struct changes
+pub const REPLAY_VERSION: u16 = 1;
+pub const REPLAY_FILE_EXTENSION: &str = "replay";
+const REPLAY_FILE_PREFIX: &str = "session-";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplayHeader {
+    pub magic: [u8; 4],
+    pub version: u16,
+    pub simulation_tick: SimulationTickConfig,
+    pub started_unix_ms: u64,
+    // rotated files of one session share started_unix_ms
+    pub part: u32,
+    // recorded kind is the index of these type names
+    pub components: Vec<String>,
+    pub events: Vec<String>
+}
+
+impl ReplayHeader {
+    #[inline]
+    pub fn component_kind<C>(&self) -> Option<u16> {
+        let name = type_name::<C>();
+        self.components.iter().position(|c| c == name).map(|i| i as u16)
+    }
+
+    #[inline]
+    pub fn event_kind<E>(&self) -> Option<u16> {
+        let name = type_name::<E>();
+        self.events.iter().position(|e| e == name).map(|i| i as u16)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedPlayer {
+    pub client_id: u64,
+    pub uuid: [u8; 16],
+    pub entity: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedComponent {
+    pub entity: u64,
+    pub kind: u16,
+    pub data: Vec<u8>
+}
+
+impl RecordedComponent {
+    #[inline]
+    pub fn decode<C: DeserializeOwned>(&self) -> anyhow::Result<C> {
+        Ok(bincode::deserialize(&self.data)?)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedEvent {
+    pub client_id: u64,
+    pub kind: u16,
+    pub data: Vec<u8>
+}
+
+impl RecordedEvent {
+    #[inline]
+    pub fn decode<E: DeserializeOwned>(&self) -> anyhow::Result<E> {
+        Ok(bincode::deserialize(&self.data)?)
+    }
+}
+
+// one simulation step.
+// events were received before the step and consumed by it,
+// components are the state after the step
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct ReplayFrame {
+    pub step: u64,
+    pub tick: u32,
+    // first frame of each file has every component,
+    // the others only changed ones
+    pub keyframe: bool,
+    pub spawned: Vec<RecordedPlayer>,
+    pub despawned: Vec<u64>,
+    pub events: Vec<RecordedEvent>,
+    pub components: Vec<RecordedComponent>
+}
+
+#[derive(Resource, Clone)]
+pub struct RecordingConfig {
+    pub directory: PathBuf,
+    // starts next part when current file exceeds this
+    pub max_file_bytes: u64,
+    // oldest files in directory are removed beyond this
+    pub max_files: usize,
+    // recording can also be started later
+    pub start_on_startup: bool
+}
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum RecordingSet {
+    Capture,
+    Write
+}
+
+// type names of recorded types, kept even when not recording
+#[derive(Resource, Default)]
+pub struct RecordingRegistry {
+    components: Vec<String>,
+    events: Vec<String>
+}
+
+struct ReplayWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    written: u64
+}
+
+impl ReplayWriter {
+    fn create(path: PathBuf, header: &ReplayHeader) -> anyhow::Result<Self> {
+        let file = File::create(&path)?;
+        let mut replay_writer = Self{
+            path,
+            writer: BufWriter::new(file),
+            written: 0
+        };
+        replay_writer.write(header)?;
+        Ok(replay_writer)
+    }
+
+    fn write<T: Serialize>(&mut self, value: &T) -> anyhow::Result<()> {
+        self.written += bincode::serialized_size(value)?;
+        bincode::serialize_into(&mut self.writer, value)?;
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub struct SessionRecorder {
+    config: RecordingConfig,
+    recording: bool,
+    writer: Option<ReplayWriter>,
+    started_unix_ms: u64,
+    part: u32,
+    step: u64,
+    frame: ReplayFrame
+}
+
+impl SessionRecorder {
+    #[inline]
+    pub fn new(config: RecordingConfig) -> Self {
+        Self{
+            config,
+            recording: false,
+            writer: None,
+            started_unix_ms: 0,
+            part: 0,
+            step: 0,
+            frame: default()
+        }
+    }
+
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    #[inline]
+    pub fn config(&self) -> &RecordingConfig {
+        &self.config
+    }
+
+    // path of the file being written
+    #[inline]
+    pub fn current_path(&self) -> Option<&Path> {
+        self.writer.as_ref().map(|w| w.path.as_path())
+    }
+
+    pub fn start(&mut self) {
+        if self.recording {
+            return;
+        }
+        self.recording = true;
+        self.started_unix_ms = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+        self.part = 0;
+        self.step = 0;
+        self.frame = ReplayFrame{
+            keyframe: true,
+            ..default()
+        };
+        info!("session recording started in: {:?}", self.config.directory);
+    }
+
+    pub fn stop(&mut self) -> anyhow::Result<()> {
+        if !self.recording {
+            return Ok(());
+        }
+        self.recording = false;
+        self.frame = default();
+        let result = self.flush();
+        if let Some(w) = self.writer.take() {
+            info!("session recording stopped: {:?}", w.path);
+        }
+        result
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(w) = self.writer.as_mut() {
+            w.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn is_keyframe(&self) -> bool {
+        self.frame.keyframe
+    }
+
+    fn write_frame(
+        &mut self,
+        tick: u32,
+        simulation_tick: SimulationTickConfig,
+        registry: &RecordingRegistry
+    ) -> anyhow::Result<()> {
+        if self.writer.is_none() {
+            self.open(simulation_tick, registry)?;
+        }
+
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.step = self.step;
+        frame.tick = tick;
+        self.step += 1;
+
+        let writer = self.writer.as_mut()
+        .ok_or_else(|| anyhow!("replay writer should be opened"))?;
+        writer.write(&frame)?;
+
+        if writer.written >= self.config.max_file_bytes {
+            writer.writer.flush()?;
+            self.writer = None;
+            self.part += 1;
+            // next part can be read without previous ones
+            self.frame.keyframe = true;
+        }
+        Ok(())
+    }
+
+    fn open(
+        &mut self,
+        simulation_tick: SimulationTickConfig,
+        registry: &RecordingRegistry
+    ) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.config.directory)?;
+        let path = self.config.directory.join(format!(
+            "{REPLAY_FILE_PREFIX}{:013}-{:04}.{REPLAY_FILE_EXTENSION}",
+            self.started_unix_ms, self.part
+        ));
+        let header = ReplayHeader{
+            magic: REPLAY_MAGIC,
+            version: REPLAY_VERSION,
+            simulation_tick,
+            started_unix_ms: self.started_unix_ms,
+            part: self.part,
+            components: registry.components.clone(),
+            events: registry.events.clone()
+        };
+        self.writer = Some(ReplayWriter::create(path.clone(), &header)?);
+        self.remove_old_files(&path)
+    }
+
+    // the file being written is never removed, whatever its name sorts as
+    fn remove_old_files(&self, current: &Path) -> anyhow::Result<()> {
+        let mut files = replay_files(&self.config.directory)?;
+        files.retain(|p| p != current);
+        let keep = self.config.max_files.saturating_sub(1);
+        if files.len() <= keep {
+            return Ok(());
+        }
+        // file names start with time so that sorted oldest first
+        let excess = files.len() - keep;
+        for path in files.drain(..excess) {
+            match fs::remove_file(&path) {
+                Ok(()) => info!("old replay file is removed: {path:?}"),
+                Err(e) => warn!("old replay file could not be removed: {path:?}: {e}")
+            }
+        }
+        Ok(())
+    }
+}
+
+// replay files written by recorder in the directory, oldest first
+pub fn replay_files(directory: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(directory)?
+    .filter_map(|entry| entry.ok().map(|e| e.path()))
+    .filter(|p| {
+        p.extension().is_some_and(|e| e == REPLAY_FILE_EXTENSION)
+        && p.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with(REPLAY_FILE_PREFIX))
+    })
+    .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+pub struct ReplayReader {
+    header: ReplayHeader,
+    reader: BufReader<File>
+}
+
+impl ReplayReader {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header: ReplayHeader = bincode::deserialize_from(&mut reader)?;
+        if header.magic != REPLAY_MAGIC {
+            bail!("not a replay file: {path:?}");
+        }
+        if header.version != REPLAY_VERSION {
+            bail!(
+                "replay version: {} is not supported, expected: {}",
+                header.version, REPLAY_VERSION
+            );
+        }
+        Ok(Self{
+            header,
+            reader
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &ReplayHeader {
+        &self.header
+    }
+
+    // None at the end of file.
+    // a frame cut by crash is treated as the end
+    pub fn next_frame(&mut self) -> anyhow::Result<Option<ReplayFrame>> {
+        match bincode::deserialize_from(&mut self.reader) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(io) if io.kind() == ErrorKind::UnexpectedEof => Ok(None),
+                e => Err(e.into())
+            }
+        }
+    }
+}
+
+pub struct SessionRecordingPlugin;
+
+impl Plugin for SessionRecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecordingRegistry>()
+        .configure_sets(FixedPostUpdate, (
+            RecordingSet::Capture,
+            RecordingSet::Write
+        ).chain().after(increment_replicon_tick_system))
+        .add_systems(Startup,
+            setup_recording.run_if(resource_exists::<RecordingConfig>)
+        )
+        .add_systems(FixedPostUpdate, (
+            record_players_system.in_set(RecordingSet::Capture),
+            write_frame_system.in_set(RecordingSet::Write)
+        ).run_if(resource_exists::<Server>.and_then(resource_exists::<SessionRecorder>)))
+        .record_component::<NetworkPlayer>();
+    }
+}
+
+pub trait RecordingAppExt {
+    fn record_component<C>(&mut self) -> &mut Self
+    where C: Component + Serialize;
+
+    fn record_client_event<E>(&mut self) -> &mut Self
+    where E: Event + Serialize + Clone;
+}
+
+impl RecordingAppExt for App {
+    fn record_component<C>(&mut self) -> &mut Self
+    where C: Component + Serialize {
+        let mut registry = self.world.get_resource_or_insert_with(RecordingRegistry::default);
+        let kind = registry.components.len() as u16;
+        registry.components.push(type_name::<C>().to_string());
+
+        self.insert_resource(RecordedKind::<C>::new(kind))
+        .add_systems(FixedPostUpdate,
+            record_component_system::<C>
+            .in_set(RecordingSet::Capture)
+            .run_if(resource_exists::<Server>.and_then(resource_exists::<SessionRecorder>))
+        )
+    }
+
+    fn record_client_event<E>(&mut self) -> &mut Self
+    where E: Event + Serialize + Clone {
+        let mut registry = self.world.get_resource_or_insert_with(RecordingRegistry::default);
+        let kind = registry.events.len() as u16;
+        registry.events.push(type_name::<E>().to_string());
+
+        // only events passed filters are recorded
+        self.accept_client_event::<E>()
+        .insert_resource(RecordedKind::<E>::new(kind))
+        .add_systems(PreUpdate,
+            record_client_event_system::<E>
+            .in_set(ServerNetstackSet::ClientEventObserve)
+            .run_if(resource_exists::<Server>.and_then(resource_exists::<SessionRecorder>))
+        )
+    }
+}
+
+#[derive(Resource)]
+struct RecordedKind<T> {
+    kind: u16,
+    phantom: PhantomData<T>
+}
+
+impl<T> RecordedKind<T> {
+    #[inline]
+    fn new(kind: u16) -> Self {
+        Self{
+            kind,
+            phantom: PhantomData
+        }
+    }
+}
+
+fn setup_recording(mut commands: Commands, config: Res<RecordingConfig>) {
+    let mut recorder = SessionRecorder::new(config.clone());
+    if config.start_on_startup {
+        recorder.start();
+    }
+    commands.insert_resource(recorder);
+    commands.remove_resource::<RecordingConfig>();
+}
+
+fn record_players_system(
+    query: Query<(Entity, Ref<NetworkPlayer>, &ServerNetworkPlayerInfo)>,
+    mut removed: RemovedComponents<NetworkPlayer>,
+    mut recorder: ResMut<SessionRecorder>
+) {
+    if !recorder.is_recording() {
+        removed.clear();
+        return;
+    }
+
+    let keyframe = recorder.is_keyframe();
+    let frame = &mut recorder.frame;
+    for (e, p, info) in query.iter() {
+        if !keyframe && !p.is_added() {
+            continue;
+        }
+        frame.spawned.push(RecordedPlayer{
+            client_id: p.client_id().get(),
+            uuid: *info.uuid().as_bytes(),
+            entity: e.to_bits()
+        });
+    }
+    frame.despawned.extend(removed.read().map(|e| e.to_bits()));
+}
+
+fn record_component_system<C: Component + Serialize>(
+    query: Query<(Entity, Ref<C>), With<Replication>>,
+    kind: Res<RecordedKind<C>>,
+    mut recorder: ResMut<SessionRecorder>,
+    mut errors: EventWriter<NetstackError>
+) {
+    if !recorder.is_recording() {
+        return;
+    }
+
+    let keyframe = recorder.is_keyframe();
+    for (e, c) in query.iter() {
+        if !keyframe && !c.is_changed() {
+            continue;
+        }
+        match bincode::serialize(&*c) {
+            Ok(data) => {
+                recorder.frame.components.push(RecordedComponent{
+                    entity: e.to_bits(),
+                    kind: kind.kind,
+                    data
+                });
+            }
+            Err(e) => {
+                errors.send(NetstackError(e.into()));
+            }
+        }
+    }
+}
+
+fn record_client_event_system<E: Event + Serialize>(
+    mut events: EventReader<AcceptedFromClient<E>>,
+    kind: Res<RecordedKind<E>>,
+    mut recorder: ResMut<SessionRecorder>,
+    mut errors: EventWriter<NetstackError>
+) {
+    if !recorder.is_recording() {
+        events.clear();
+        return;
+    }
+
+    for AcceptedFromClient { client_id, event } in events.read() {
+        match bincode::serialize(event) {
+            Ok(data) => {
+                recorder.frame.events.push(RecordedEvent{
+                    client_id: client_id.get(),
+                    kind: kind.kind,
+                    data
+                });
+            }
+            Err(e) => {
+                errors.send(NetstackError(e.into()));
+            }
+        }
+    }
+}
+
+fn write_frame_system(
+    mut recorder: ResMut<SessionRecorder>,
+    registry: Res<RecordingRegistry>,
+    simulation_tick: Res<SimulationTickConfig>,
+    replicon_tick: Res<RepliconTick>,
+    mut errors: EventWriter<NetstackError>
+) {
+    if !recorder.is_recording() {
+        return;
+    }
+
+    if let Err(e) = recorder.write_frame(replicon_tick.get(), *simulation_tick, &registry) {
+        errors.send(NetstackError(anyhow!("session recording is stopped: {e}")));
+        let _ = recorder.stop();
+    }
+}
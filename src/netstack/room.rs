@@ -0,0 +1,341 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{prelude::*, server::connected_clients::ConnectedClients};
+use bevy_replicon_renet::renet::transport::NetcodeServerTransport;
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail};
+use super::{
+    admin::AdminCommandAppExt,
+    components::NetworkPlayer,
+    error::NetstackError,
+    loopback::LoopbackServerTransport,
+    resources::PlayerEntityMap,
+    server::{client_user_data, handle_server_event_system, Server},
+    visibility::{PendingVisibility, VisibilitySet}
+};
+
+// every client joins here unless it asks for another room
+pub const DEFAULT_ROOM: RoomId = RoomId(0);
+// user_data[16..20] is little endian room id requested on connect
+pub const ROOM_USER_DATA_RANGE: std::ops::Range<usize> = 16..20;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct RoomId(pub u32);
+
+// entities without this are visible from every room
+#[derive(Component, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct InRoom(pub RoomId);
+
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+pub struct RoomJoinRequest {
+    pub room: RoomId
+}
+
+pub struct RoomInfo {
+    pub name: String,
+    // 0 for unlimited
+    pub max_clients: usize,
+    members: HashSet<ClientId>
+}
+
+impl RoomInfo {
+    #[inline]
+    pub fn members(&self) -> &HashSet<ClientId> {
+        &self.members
+    }
+
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.max_clients > 0 && self.members.len() >= self.max_clients
+    }
+}
+
+#[derive(Resource)]
+pub struct Rooms {
+    rooms: HashMap<RoomId, RoomInfo>,
+    clients: HashMap<ClientId, RoomId>,
+    next_id: u32,
+    destroyed: Vec<RoomId>
+}
+
+impl Default for Rooms {
+    fn default() -> Self {
+        let mut rooms = HashMap::default();
+        rooms.insert(DEFAULT_ROOM, RoomInfo{
+            name: "default".to_string(),
+            max_clients: 0,
+            members: default()
+        });
+        Self{
+            rooms,
+            clients: default(),
+            next_id: DEFAULT_ROOM.0 + 1,
+            destroyed: vec![]
+        }
+    }
+}
+
+impl Rooms {
+    pub fn create(&mut self, name: String, max_clients: usize) -> RoomId {
+        let id = RoomId(self.next_id);
+        self.next_id += 1;
+        self.rooms.insert(id, RoomInfo{
+            name,
+            max_clients,
+            members: default()
+        });
+        info!("room: {id:?} is created");
+        id
+    }
+
+    // members move to default room and entities in the room are despawned
+    pub fn destroy(&mut self, id: RoomId) -> anyhow::Result<()> {
+        if id == DEFAULT_ROOM {
+            bail!("default room can not be destroyed");
+        }
+        if !self.rooms.contains_key(&id) {
+            bail!("room: {id:?} does not exist");
+        }
+        self.destroyed.push(id);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn get(&self, id: &RoomId) -> Option<&RoomInfo> {
+        self.rooms.get(id)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&RoomId, &RoomInfo)> {
+        self.rooms.iter()
+    }
+
+    #[inline]
+    pub fn client_room(&self, client_id: &ClientId) -> Option<RoomId> {
+        self.clients.get(client_id).copied()
+    }
+
+    fn join(&mut self, client_id: ClientId, id: RoomId) -> anyhow::Result<()> {
+        let room = self.rooms.get(&id)
+        .ok_or_else(|| anyhow!("room: {id:?} does not exist"))?;
+        if room.is_full() && !room.members.contains(&client_id) {
+            bail!("room: {id:?} is full");
+        }
+
+        self.leave(&client_id);
+        if let Some(room) = self.rooms.get_mut(&id) {
+            room.members.insert(client_id);
+        }
+        self.clients.insert(client_id, id);
+        Ok(())
+    }
+
+    fn leave(&mut self, client_id: &ClientId) {
+        if let Some(id) = self.clients.remove(client_id) {
+            if let Some(room) = self.rooms.get_mut(&id) {
+                room.members.remove(client_id);
+            }
+        }
+    }
+}
+
+#[inline]
+pub fn is_visible_in_room(entity_room: Option<&InRoom>, client_room: Option<RoomId>) -> bool {
+    match entity_room {
+        None => true,
+        Some(InRoom(room)) => client_room == Some(*room)
+    }
+}
+
+pub struct RoomPlugin;
+
+impl Plugin for RoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rooms>()
+        .replicate::<InRoom>()
+        .add_client_event::<RoomJoinRequest>(ChannelKind::Ordered)
+        .add_admin_command("rooms", "rooms", rooms_command)
+        .add_admin_command("room", "room <create <name> [max clients]|destroy <id>>", room_command)
+        .add_systems(Update, (
+            assign_room_system.after(handle_server_event_system),
+            room_join_request_system,
+            destroy_rooms_system
+        ).chain().run_if(resource_exists::<Server>))
+        .add_systems(PostUpdate,
+            room_visibility_system
+            .in_set(VisibilitySet::Room)
+            .run_if(resource_exists::<Server>)
+        );
+    }
+}
+
+fn requested_room(user_data: &[u8; 256]) -> RoomId {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&user_data[ROOM_USER_DATA_RANGE]);
+    RoomId(u32::from_le_bytes(bytes))
+}
+
+fn assign_room_system(
+    mut commands: Commands,
+    mut server_events: EventReader<ServerEvent>,
+    mut rooms: ResMut<Rooms>,
+    player_entities: Res<PlayerEntityMap>,
+    netcode_server: Option<Res<NetcodeServerTransport>>,
+    loopback_server: Option<Res<LoopbackServerTransport>>,
+    mut errors: EventWriter<NetstackError>
+) {
+    for e in server_events.read() {
+        match e {
+            ServerEvent::ClientConnected { client_id } => {
+                let requested = client_user_data(
+                    netcode_server.as_deref(),
+                    loopback_server.as_deref(),
+                    client_id
+                )
+                .map(|u| requested_room(&u))
+                .unwrap_or(DEFAULT_ROOM);
+
+                let room = match rooms.join(*client_id, requested) {
+                    Ok(()) => requested,
+                    Err(e) => {
+                        warn!("client: {client_id:?} can not join requested room: {e}");
+                        if let Err(e) = rooms.join(*client_id, DEFAULT_ROOM) {
+                            errors.send(NetstackError(e));
+                            continue;
+                        }
+                        DEFAULT_ROOM
+                    }
+                };
+
+                if let Some(entity) = player_entities.get(client_id) {
+                    commands.entity(*entity).insert(InRoom(room));
+                }
+                info!("client: {client_id:?} joined room: {room:?}");
+            }
+            ServerEvent::ClientDisconnected { client_id, .. } => {
+                rooms.leave(client_id);
+            }
+        }
+    }
+}
+
+fn room_join_request_system(
+    mut requests: EventReader<FromClient<RoomJoinRequest>>,
+    mut rooms: ResMut<Rooms>,
+    player_entities: Res<PlayerEntityMap>,
+    mut query: Query<&mut InRoom, With<NetworkPlayer>>
+) {
+    for FromClient { client_id, event } in requests.read() {
+        if let Err(e) = rooms.join(*client_id, event.room) {
+            warn!("client: {client_id:?} join request is rejected: {e}");
+            continue;
+        }
+
+        if let Some(mut in_room) = player_entities.get(client_id)
+        .and_then(|e| query.get_mut(*e).ok()) {
+            in_room.0 = event.room;
+        }
+        info!("client: {client_id:?} moved to room: {:?}", event.room);
+    }
+}
+
+fn destroy_rooms_system(
+    mut commands: Commands,
+    mut rooms: ResMut<Rooms>,
+    mut query: Query<(Entity, &mut InRoom, Option<&NetworkPlayer>)>
+) {
+    if rooms.destroyed.is_empty() {
+        return;
+    }
+
+    let destroyed = std::mem::take(&mut rooms.destroyed);
+    for (e, mut in_room, player) in query.iter_mut() {
+        if !destroyed.contains(&in_room.0) {
+            continue;
+        }
+        match player {
+            Some(p) => {
+                if rooms.join(p.client_id(), DEFAULT_ROOM).is_ok() {
+                    in_room.0 = DEFAULT_ROOM;
+                }
+            }
+            None => commands.entity(e).despawn_recursive()
+        }
+    }
+    for id in destroyed {
+        rooms.rooms.remove(&id);
+        info!("room: {id:?} is destroyed");
+    }
+}
+
+// visibility by room, written only for clients whose room changed
+// and for entities whose room changed, interest narrows it down afterwards
+fn room_visibility_system(
+    query: Query<(Entity, Option<&InRoom>), With<Replication>>,
+    changed: Query<
+        (Entity, Option<&InRoom>),
+        (With<Replication>, Or<(Added<Replication>, Changed<InRoom>)>)
+    >,
+    mut removed: RemovedComponents<InRoom>,
+    rooms: Res<Rooms>,
+    connected_clients: Res<ConnectedClients>,
+    mut pending: ResMut<PendingVisibility>,
+    mut client_rooms: Local<HashMap<ClientId, Option<RoomId>>>
+) {
+    let connected = connected_clients.iter().map(|c| c.id()).collect::<HashSet<_>>();
+    client_rooms.retain(|client_id, _| connected.contains(client_id));
+
+    // new clients see nothing yet
+    let mut moved = HashSet::<ClientId>::default();
+    for client_id in connected.iter() {
+        let room = rooms.client_room(client_id);
+        if client_rooms.insert(*client_id, room) != Some(room) {
+            moved.insert(*client_id);
+            for (e, in_room) in query.iter() {
+                pending.set(*client_id, e, is_visible_in_room(in_room, room));
+            }
+        }
+    }
+
+    // entities which left every room are visible from all of them
+    let left = removed.read()
+    .filter_map(|e| query.get(e).ok())
+    .collect::<Vec<_>>();
+    for (e, in_room) in changed.iter().chain(left) {
+        for client_id in connected.iter().filter(|c| !moved.contains(*c)) {
+            let room = rooms.client_room(client_id);
+            pending.set(*client_id, e, is_visible_in_room(in_room, room));
+        }
+    }
+}
+
+fn rooms_command(world: &mut World, _: &[&str]) -> anyhow::Result<String> {
+    let rooms = world.resource::<Rooms>();
+    let mut lines = rooms.iter()
+    .map(|(id, r)| format!(
+        "{} {} clients: {}/{}",
+        id.0, r.name, r.members.len(),
+        if r.max_clients == 0 { "-".to_string() } else { r.max_clients.to_string() }
+    ))
+    .collect::<Vec<_>>();
+    lines.sort();
+    Ok(lines.join("\n"))
+}
+
+fn room_command(world: &mut World, args: &[&str]) -> anyhow::Result<String> {
+    let mut rooms = world.resource_mut::<Rooms>();
+    match args {
+        ["create", name] => {
+            let id = rooms.create(name.to_string(), 0);
+            Ok(format!("created room: {}", id.0))
+        }
+        ["create", name, max_clients] => {
+            let id = rooms.create(name.to_string(), max_clients.parse()?);
+            Ok(format!("created room: {}", id.0))
+        }
+        ["destroy", id] => {
+            rooms.destroy(RoomId(id.parse()?))?;
+            Ok(format!("destroying room: {id}"))
+        }
+        _ => bail!("expected create or destroy")
+    }
+}
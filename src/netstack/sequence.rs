@@ -0,0 +1,89 @@
+use std::cmp::Ordering;
+use bevy::prelude::*;
+use serde::{Serialize, Deserialize};
+
+// unsigned value wrapping around, compared within half of its range
+pub trait SequenceValue: Copy + Eq + Default {
+    fn wrapping_increment(self) -> Self;
+    fn wrapping_distance(self, other: Self) -> i64;
+}
+
+macro_rules! impl_sequence_value {
+    ($unsigned:ty, $signed:ty) => {
+        impl SequenceValue for $unsigned {
+            #[inline]
+            fn wrapping_increment(self) -> Self {
+                self.wrapping_add(1)
+            }
+
+            #[inline]
+            fn wrapping_distance(self, other: Self) -> i64 {
+                self.wrapping_sub(other) as $signed as i64
+            }
+        }
+    };
+}
+
+impl_sequence_value!(u16, i16);
+impl_sequence_value!(u32, i32);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Sequence<T>(T);
+
+impl<T: SequenceValue> Sequence<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    #[inline]
+    pub fn get(&self) -> T {
+        self.0
+    }
+
+    #[inline]
+    pub fn next(&self) -> Self {
+        Self(self.0.wrapping_increment())
+    }
+
+    // positive when self is after other
+    #[inline]
+    pub fn distance(&self, other: &Self) -> i64 {
+        self.0.wrapping_distance(other.0)
+    }
+
+    #[inline]
+    pub fn is_newer_than(&self, other: &Self) -> bool {
+        self.distance(other) > 0
+    }
+
+    // not total order, only valid for values within half of the range
+    #[inline]
+    pub fn cmp_wrapping(&self, other: &Self) -> Ordering {
+        self.distance(other).cmp(&0)
+    }
+}
+
+// u32 wraps after years of inputs at frame rate,
+// same width on every architecture unlike usize
+pub type InputSequence = Sequence<u32>;
+
+// events carrying their input sequence number
+pub trait SequencedEvent {
+    fn sequence(&self) -> InputSequence;
+}
+
+// client side generator, reset for each connection
+#[derive(Resource, Default)]
+pub struct InputSequencer {
+    next: InputSequence
+}
+
+impl InputSequencer {
+    #[inline]
+    pub fn next(&mut self) -> InputSequence {
+        let sequence = self.next;
+        self.next = sequence.next();
+        sequence
+    }
+}
@@ -1,5 +1,5 @@
 use std::{net::{IpAddr, SocketAddr, UdpSocket}, time::SystemTime};
-use bevy::{prelude::*, utils::Uuid};
+use bevy::{prelude::*, utils::{HashSet, Uuid}};
 use bevy_replicon::prelude::*;
 use bevy_replicon_renet::{
     renet::{
@@ -12,42 +12,109 @@ use bevy_replicon_renet::renet::transport::ServerConfig as RenetServerConfig;
 use bevy_replicon_renet::renet::ClientId as RenetClientId;
 use bevy_replicon_snap::RepliconSnapPlugin;
 use super::{
+    admin::AdminPlugin,
     components::{ServerNetworkPlayerInfo, NetworkPlayer}, 
+    conditioner::{spawn_udp_link_conditioner, LinkConditionsHandle, UdpLinkConditioner},
     error::{on_transport_error_system, NetstackError}, 
-    resources::{OwnedEntityMap, PlayerEntityMap}
+    lifecycle::{DisconnectClient, LifecyclePlugin},
+    loopback::{LoopbackServerPlugin, LoopbackServerTransport},
+    metrics::MetricsPlugin,
+    notification::NotificationPlugin,
+    priority::{ReplicationBudget, ReplicationPriorityPlugin},
+    recording::SessionRecordingPlugin,
+    resources::{OwnedEntityMap, PlayerEntityMap},
+    room::RoomPlugin,
+    stats::NetworkStatsPlugin,
+    tick::{SimulationTickConfig, SimulationTickPlugin},
+    time_sync::TimeSyncPlugin,
+    transport::NetstackTransport,
+    visibility::VisibilityPlugin
 };
 use anyhow::anyhow;
 
 #[derive(Resource)]
 pub struct ServerConfig {
-    pub network_tick_rate: u16,
+    pub simulation_tick_rate: u16,
+    // replicate once every this simulation ticks
+    pub replication_interval: u16,
     pub listen_addr: IpAddr,
     pub listen_port: u16,
     pub protocol_id: u64,
     pub private_key: [u8; 32],
-    pub max_clients: usize
+    pub max_clients: usize,
+    pub transport: NetstackTransport,
+    pub replication_budget: ReplicationBudget
 }
 
 #[derive(Resource)]
 pub struct Server;
 
+// user_data[32..64] is utf-8 display name padded with zeros
+pub const DISPLAY_NAME_USER_DATA_RANGE: std::ops::Range<usize> = 32..64;
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ServerNetstackSet {
+    // FromClient events are copied into ClientEventQueue
+    ClientEventCollect,
+    // systems merging redundantly sent client inputs into ClientEventQueue
+    ClientInputMerge,
+    // systems inspecting or filtering ClientEventQueue
+    // before they are read by gameplay
+    ClientEventFilter,
+    // ClientEventQueue is sent as AcceptedFromClient
+    ClientEventAccept,
+    // systems observing client events which passed filters
+    ClientEventObserve
+}
+
 pub struct ServerNetstackPlugin;
 
 impl Plugin for ServerNetstackPlugin {
     fn build(&self, app: &mut App) {
         let params = app.world.resource::<ServerConfig>();
-        app.add_plugins((
+        let simulation_tick = SimulationTickConfig{
+            tick_rate: params.simulation_tick_rate,
+            replication_interval: params.replication_interval
+        };
+        let replication_budget = params.replication_budget.clone();
+        // replicon tick is incremented by simulation tick plugin
+        app.insert_resource(simulation_tick)
+        .insert_resource(replication_budget)
+        .add_plugins((
             RepliconPlugins.build().disable::<ClientPlugin>().set(ServerPlugin{
-                tick_policy: TickPolicy::MaxTickRate(params.network_tick_rate),
+                tick_policy: TickPolicy::Manual,
+                // visibility is granted by room and interest systems
+                visibility_policy: VisibilityPolicy::Whitelist,
                 ..default()
             }),
             RepliconRenetPlugins.build().disable::<RepliconRenetClientPlugin>(),
             RepliconSnapPlugin
         ))
+        .add_plugins((
+            TimeSyncPlugin,
+            SimulationTickPlugin,
+            LoopbackServerPlugin,
+            NetworkStatsPlugin,
+            MetricsPlugin,
+            LifecyclePlugin,
+            SessionRecordingPlugin,
+            AdminPlugin,
+            RoomPlugin,
+            VisibilityPlugin,
+            ReplicationPriorityPlugin,
+            NotificationPlugin
+        ))
         .add_event::<NetstackError>()
         .init_resource::<PlayerEntityMap>()
         .init_resource::<OwnedEntityMap>()
         .replicate::<NetworkPlayer>()
+        .configure_sets(PreUpdate, (
+            ServerNetstackSet::ClientEventCollect,
+            ServerNetstackSet::ClientInputMerge,
+            ServerNetstackSet::ClientEventFilter,
+            ServerNetstackSet::ClientEventAccept,
+            ServerNetstackSet::ClientEventObserve
+        ).chain().after(ServerSet::Receive))
         .add_systems(Startup, setup_server)
         .add_systems(Update, (
             handle_server_event_system,
@@ -60,6 +127,7 @@ fn setup_server(
     mut commands: Commands, 
     net_channels: Res<RepliconChannels>,
     config: Res<ServerConfig>,
+    link_conditions: Option<Res<LinkConditionsHandle>>,
     mut errors: EventWriter<NetstackError>
 ) {
     let renet_server = RenetServer::new(ConnectionConfig{
@@ -68,25 +136,58 @@ fn setup_server(
         ..default()
     });
 
-    let netcode_transport = match setup_transport(&config) {
-        Ok(t) => t,
-        Err(e) => {
-            errors.send(NetstackError(e));
-            return;
+    match &config.transport {
+        NetstackTransport::Udp => {
+            let (netcode_transport, relay) = match setup_transport(
+                &config, 
+                link_conditions.as_deref()
+            ) {
+                Ok(t) => t,
+                Err(e) => {
+                    errors.send(NetstackError(e));
+                    return;
+                }
+            };
+            info!("server is listening at {}:{}", config.listen_addr, config.listen_port);
+            commands.insert_resource(netcode_transport);
+            // relay thread lives as long as this resource
+            if let Some(relay) = relay {
+                commands.insert_resource(relay);
+            }
         }
-    };
+        NetstackTransport::Loopback(network) => {
+            if let Some(link_conditions) = link_conditions {
+                network.use_link_conditions(link_conditions.clone());
+            }
+            info!("server is listening on loopback network");
+            commands.insert_resource(LoopbackServerTransport::new(network.clone()));
+        }
+    }
 
-    info!("server is listening at {}:{}", config.listen_addr, config.listen_port);
     commands.remove_resource::<ServerConfig>();
     commands.insert_resource(Server); 
     commands.insert_resource(renet_server);
-    commands.insert_resource(netcode_transport);
 }
 
-fn setup_transport(config: &ServerConfig) 
--> anyhow::Result<NetcodeServerTransport> {
+fn setup_transport(
+    config: &ServerConfig, 
+    link_conditions: Option<&LinkConditionsHandle>
+) -> anyhow::Result<(NetcodeServerTransport, Option<UdpLinkConditioner>)> {
     let listen_addr = SocketAddr::new(config.listen_addr, config.listen_port);
-    let socket = UdpSocket::bind(listen_addr)?;
+    let (socket, relay) = match link_conditions {
+        // conditioner takes listen address and relays to the server socket
+        Some(link_conditions) => {
+            let socket = UdpSocket::bind((config.listen_addr, 0))?;
+            let relay = spawn_udp_link_conditioner(
+                listen_addr, 
+                socket.local_addr()?, 
+                link_conditions.clone()
+            )?;
+            warn!("link conditioner is enabled: {:?}", link_conditions.get());
+            (socket, Some(relay))
+        }
+        None => (UdpSocket::bind(listen_addr)?, None)
+    };
     let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
     let netcode_transport = NetcodeServerTransport::new(RenetServerConfig{
         current_time,
@@ -97,21 +198,60 @@ fn setup_transport(config: &ServerConfig)
         },
         public_addresses: vec![listen_addr]
     }, socket)?;
-    Ok(netcode_transport)
+    Ok((netcode_transport, relay))
+}
+
+pub(crate) fn client_user_data(
+    netcode_server: Option<&NetcodeServerTransport>,
+    loopback_server: Option<&LoopbackServerTransport>,
+    client_id: &ClientId
+) -> Option<[u8; 256]> {
+    if let Some(netcode_server) = netcode_server {
+        return netcode_server.user_data(RenetClientId::from_raw(client_id.get()));
+    }
+    loopback_server?.user_data(client_id.get())
 }
 
-fn handle_server_event_system(
+// control characters are dropped, user data is written by the client
+pub fn display_name_from_user_data(user_data: &[u8; 256]) -> String {
+    let bytes = &user_data[DISPLAY_NAME_USER_DATA_RANGE];
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len])
+    .chars()
+    .filter(|c| !c.is_control())
+    .collect::<String>()
+    .trim()
+    .to_string()
+}
+
+// systems rejecting clients on connect run before this
+// and send DisconnectClient with a rejected notice, those players are not spawned
+pub(crate) fn handle_server_event_system(
     mut commands: Commands,
     mut events: EventReader<ServerEvent>,
+    mut disconnects: EventReader<DisconnectClient>,
     mut palyer_entities: ResMut<PlayerEntityMap>,
-    netcode_server: Res<NetcodeServerTransport>, 
+    netcode_server: Option<Res<NetcodeServerTransport>>, 
+    loopback_server: Option<Res<LoopbackServerTransport>>,
     mut errors: EventWriter<NetstackError> 
 ) {
+    let rejected = disconnects.read()
+    .filter(|d| d.notice.rejected)
+    .map(|d| d.client_id)
+    .collect::<HashSet<_>>();
+
     for e in events.read() {
         match e {
             ServerEvent::ClientConnected { client_id } => {
-                let user_data = match netcode_server.user_data(
-                    RenetClientId::from_raw(client_id.get())
+                if rejected.contains(client_id) {
+                    info!("client: {client_id:?} is rejected on connect");
+                    continue;
+                }
+
+                let user_data = match client_user_data(
+                    netcode_server.as_deref(),
+                    loopback_server.as_deref(),
+                    client_id
                 ) {
                     Some(u) => u,
                     None => {
@@ -132,7 +272,8 @@ fn handle_server_event_system(
 
                 let entity = commands
                     .spawn((
-                        ServerNetworkPlayerInfo::new(uuid),
+                        ServerNetworkPlayerInfo::new(uuid)
+                        .with_display_name(display_name_from_user_data(&user_data)),
                         NetworkPlayer::new(*client_id)
                     ))
                     .id();
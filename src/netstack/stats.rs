@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+use bevy::{prelude::*, utils::HashMap};
+use bevy_replicon::{client::diagnostics::ClientStats, prelude::*};
+use bevy_replicon_renet::renet::{NetworkInfo, RenetClient, RenetServer};
+use super::tick::SimulationTickConfig;
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct NetworkStatsConfig {
+    // samples older than this are dropped from summaries
+    pub window_seconds: f32,
+    // summary is logged every this seconds, never when zero
+    pub log_interval_seconds: f32
+}
+
+impl Default for NetworkStatsConfig {
+    fn default() -> Self {
+        Self{
+            window_seconds: 5.0,
+            log_interval_seconds: 10.0
+        }
+    }
+}
+
+// renet network info of one connection at one frame
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NetworkSample {
+    pub rtt: f64,
+    pub packet_loss: f64,
+    pub bytes_sent_per_second: f64,
+    pub bytes_received_per_second: f64
+}
+
+impl From<NetworkInfo> for NetworkSample {
+    fn from(info: NetworkInfo) -> Self {
+        Self{
+            rtt: info.rtt,
+            packet_loss: info.packet_loss,
+            bytes_sent_per_second: info.bytes_sent_per_second,
+            bytes_received_per_second: info.bytes_received_per_second
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NetworkSummary {
+    pub samples: usize,
+    pub rtt_mean: f64,
+    pub rtt_max: f64,
+    pub packet_loss_mean: f64,
+    pub bytes_sent_per_second_mean: f64,
+    pub bytes_received_per_second_mean: f64
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct ConnectionStats {
+    latest: NetworkSample,
+    window: VecDeque<(f32, NetworkSample)>
+}
+
+impl ConnectionStats {
+    #[inline]
+    pub fn latest(&self) -> &NetworkSample {
+        &self.latest
+    }
+
+    pub fn summary(&self) -> NetworkSummary {
+        let samples = self.window.len();
+        if samples == 0 {
+            return NetworkSummary::default();
+        }
+
+        let count = samples as f64;
+        let mut summary = NetworkSummary{
+            samples,
+            ..default()
+        };
+        for (_, s) in self.window.iter() {
+            summary.rtt_mean += s.rtt / count;
+            summary.rtt_max = summary.rtt_max.max(s.rtt);
+            summary.packet_loss_mean += s.packet_loss / count;
+            summary.bytes_sent_per_second_mean += s.bytes_sent_per_second / count;
+            summary.bytes_received_per_second_mean += s.bytes_received_per_second / count;
+        }
+        summary
+    }
+
+    fn push(&mut self, now: f32, window_seconds: f32, sample: NetworkSample) {
+        self.latest = sample;
+        self.window.push_back((now, sample));
+        while self.window.front().is_some_and(|(t, _)| now - *t > window_seconds) {
+            self.window.pop_front();
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct ReplicationStats {
+    // (time, bytes, messages)
+    window: VecDeque<(f32, u64, u64)>
+}
+
+impl ReplicationStats {
+    #[inline]
+    pub fn bytes_per_second(&self, window_seconds: f32) -> f64 {
+        if window_seconds <= 0.0 {
+            return 0.0;
+        }
+        self.window.iter().map(|(_, b, _)| *b as f64).sum::<f64>() / window_seconds as f64
+    }
+
+    // on client a replication message is received once every network tick
+    #[inline]
+    pub fn bytes_per_message(&self) -> f64 {
+        let messages = self.window.iter().map(|(_, _, m)| *m).sum::<u64>();
+        if messages == 0 {
+            return 0.0;
+        }
+        self.window.iter().map(|(_, b, _)| *b as f64).sum::<f64>() / messages as f64
+    }
+
+    fn push(&mut self, now: f32, window_seconds: f32, bytes: u64, messages: u64) {
+        self.window.push_back((now, bytes, messages));
+        while self.window.front().is_some_and(|(t, _, _)| now - *t > window_seconds) {
+            self.window.pop_front();
+        }
+    }
+}
+
+// connections are keyed by client id on server,
+// client has only ClientId::SERVER
+#[derive(Resource, Default, Debug)]
+pub struct NetworkStats {
+    connections: HashMap<ClientId, ConnectionStats>,
+    replication: ReplicationStats,
+    window_seconds: f32
+}
+
+impl NetworkStats {
+    #[inline]
+    pub fn get(&self, client_id: &ClientId) -> Option<&ConnectionStats> {
+        self.connections.get(client_id)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&ClientId, &ConnectionStats)> {
+        self.connections.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    #[inline]
+    pub fn replication(&self) -> &ReplicationStats {
+        &self.replication
+    }
+
+    // replicon measures received replication only on client,
+    // server replication is estimated from all bytes sent by renet
+    #[inline]
+    pub fn is_replication_estimated(&self) -> bool {
+        self.replication.window.is_empty()
+    }
+
+    pub fn replication_bytes_per_second(&self) -> f64 {
+        if !self.replication.window.is_empty() {
+            return self.replication.bytes_per_second(self.window_seconds);
+        }
+        self.connections.values()
+        .map(|c| c.summary().bytes_sent_per_second_mean)
+        .sum()
+    }
+
+    // estimated on server as sent bytes per network tick, including
+    // events and protocol overhead, see is_replication_estimated
+    pub fn replication_bytes_per_tick(&self, simulation_tick: Option<&SimulationTickConfig>) -> f64 {
+        if !self.replication.window.is_empty() {
+            return self.replication.bytes_per_message();
+        }
+        let Some(simulation_tick) = simulation_tick else {
+            return 0.0;
+        };
+        self.replication_bytes_per_second() / simulation_tick.network_tick_rate() as f64
+    }
+}
+
+pub struct NetworkStatsPlugin;
+
+impl Plugin for NetworkStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetworkStatsConfig>()
+        .init_resource::<NetworkStats>()
+        .add_systems(PostUpdate, (
+            server_network_stats_system.run_if(resource_exists::<RenetServer>),
+            client_network_stats_system.run_if(resource_exists::<RenetClient>),
+            log_network_stats_system
+        ).chain());
+    }
+}
+
+fn server_network_stats_system(
+    renet_server: Res<RenetServer>,
+    config: Res<NetworkStatsConfig>,
+    mut stats: ResMut<NetworkStats>,
+    time: Res<Time<Real>>
+) {
+    let now = time.elapsed_seconds();
+    stats.window_seconds = config.window_seconds;
+    let mut connected = Vec::with_capacity(stats.connections.len());
+    for renet_client_id in renet_server.clients_id() {
+        let Ok(info) = renet_server.network_info(renet_client_id) else {
+            continue;
+        };
+        let client_id = ClientId::new(renet_client_id.raw());
+        stats.connections.entry(client_id)
+        .or_default()
+        .push(now, config.window_seconds, info.into());
+        connected.push(client_id);
+    }
+    stats.connections.retain(|client_id, _| connected.contains(client_id));
+}
+
+fn client_network_stats_system(
+    renet_client: Res<RenetClient>,
+    replicon_stats: Option<Res<ClientStats>>,
+    config: Res<NetworkStatsConfig>,
+    mut stats: ResMut<NetworkStats>,
+    mut received: Local<(u64, u64)>,
+    time: Res<Time<Real>>
+) {
+    if !renet_client.is_connected() {
+        stats.connections.clear();
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    stats.window_seconds = config.window_seconds;
+    stats.connections.entry(ClientId::SERVER)
+    .or_default()
+    .push(now, config.window_seconds, renet_client.network_info().into());
+
+    if let Some(replicon_stats) = replicon_stats {
+        let bytes = replicon_stats.bytes as u64;
+        let messages = replicon_stats.packets as u64;
+        let (last_bytes, last_messages) = *received;
+        stats.replication.push(
+            now,
+            config.window_seconds,
+            bytes.saturating_sub(last_bytes),
+            messages.saturating_sub(last_messages)
+        );
+        *received = (bytes, messages);
+    }
+}
+
+fn log_network_stats_system(
+    stats: Res<NetworkStats>,
+    config: Res<NetworkStatsConfig>,
+    simulation_tick: Option<Res<SimulationTickConfig>>,
+    mut elapsed: Local<f32>,
+    time: Res<Time<Real>>
+) {
+    if config.log_interval_seconds <= 0.0 {
+        return;
+    }
+    *elapsed += time.delta_seconds();
+    if *elapsed < config.log_interval_seconds {
+        return;
+    }
+    *elapsed = 0.0;
+
+    for (client_id, connection) in stats.iter() {
+        let s = connection.summary();
+        info!(
+            "network stats: {:?} rtt mean: {:.1}ms max: {:.1}ms loss: {:.2}% sent: {:.0}B/s received: {:.0}B/s",
+            client_id,
+            s.rtt_mean * 1000.0, s.rtt_max * 1000.0,
+            s.packet_loss_mean * 100.0,
+            s.bytes_sent_per_second_mean, s.bytes_received_per_second_mean
+        );
+    }
+    if !stats.is_empty() {
+        let label = if stats.is_replication_estimated() {
+            "replication (estimated from sent bytes)"
+        } else {
+            "replication"
+        };
+        info!(
+            "{label}: {:.0}B/s {:.0}B/tick",
+            stats.replication_bytes_per_second(),
+            stats.replication_bytes_per_tick(simulation_tick.as_deref())
+        );
+    }
+}
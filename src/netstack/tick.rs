@@ -0,0 +1,125 @@
+use bevy::prelude::*;
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+use serde::{Serialize, Deserialize};
+use super::{client::Client, server::Server};
+
+// server runs simulation on FixedUpdate at tick_rate
+// and replicates once every replication_interval simulation ticks,
+// so network tick is counted in replicon tick
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SimulationTickConfig {
+    pub tick_rate: u16,
+    pub replication_interval: u16
+}
+
+impl SimulationTickConfig {
+    #[inline]
+    pub fn tick_delta(&self) -> f32 {
+        1.0 / self.tick_rate as f32
+    }
+
+    #[inline]
+    pub fn network_tick_rate(&self) -> f32 {
+        self.tick_rate as f32 / self.replication_interval as f32
+    }
+
+    #[inline]
+    pub fn network_tick_delta(&self) -> f32 {
+        self.replication_interval as f32 / self.tick_rate as f32
+    }
+}
+
+// sent to client on connect
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct SimulationTickSync {
+    pub config: SimulationTickConfig
+}
+
+pub struct SimulationTickPlugin;
+
+impl Plugin for SimulationTickPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(config) = app.world.get_resource::<SimulationTickConfig>() {
+            let tick_rate = config.tick_rate;
+            app.insert_resource(Time::<Fixed>::from_hz(tick_rate as f64));
+        }
+
+        app.add_server_event::<SimulationTickSync>(ChannelKind::Ordered)
+        .add_systems(FixedPostUpdate,
+            increment_replicon_tick_system.run_if(resource_exists::<Server>)
+        )
+        .add_systems(Update, (
+            apply_simulation_tick_config_system,
+            send_simulation_tick_sync_system
+        ).run_if(resource_exists::<Server>))
+        .add_systems(PreUpdate,
+            receive_simulation_tick_sync_system
+            .after(ClientSet::Receive)
+            .run_if(resource_exists::<Client>)
+        );
+    }
+}
+
+pub(crate) fn increment_replicon_tick_system(
+    mut simulation_ticks: Local<u16>,
+    config: Res<SimulationTickConfig>,
+    mut replicon_tick: ResMut<RepliconTick>
+) {
+    *simulation_ticks += 1;
+    if *simulation_ticks >= config.replication_interval {
+        *simulation_ticks = 0;
+        replicon_tick.increment();
+    }
+}
+
+// keeps fixed timestep following config when it is changed at runtime
+fn apply_simulation_tick_config_system(
+    config: Res<SimulationTickConfig>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut syncs: EventWriter<ToClients<SimulationTickSync>>
+) {
+    if !config.is_changed() || config.is_added() {
+        return;
+    }
+
+    fixed_time.set_timestep_hz(config.tick_rate as f64);
+    syncs.send(ToClients{
+        mode: SendMode::Broadcast,
+        event: SimulationTickSync{ config: *config }
+    });
+    info!(
+        "simulation tick rate: {} network tick rate: {}",
+        config.tick_rate, config.network_tick_rate()
+    );
+}
+
+fn send_simulation_tick_sync_system(
+    mut server_events: EventReader<ServerEvent>,
+    config: Res<SimulationTickConfig>,
+    mut syncs: EventWriter<ToClients<SimulationTickSync>>
+) {
+    for e in server_events.read() {
+        if let ServerEvent::ClientConnected { client_id } = e {
+            syncs.send(ToClients{
+                mode: SendMode::Direct(*client_id),
+                event: SimulationTickSync{ config: *config }
+            });
+        }
+    }
+}
+
+fn receive_simulation_tick_sync_system(
+    mut commands: Commands,
+    mut syncs: EventReader<SimulationTickSync>,
+    mut fixed_time: ResMut<Time<Fixed>>
+) {
+    for sync in syncs.read() {
+        let config = sync.config;
+        fixed_time.set_timestep_hz(config.tick_rate as f64);
+        commands.insert_resource(config);
+        info!(
+            "synced simulation tick rate: {} network tick rate: {}",
+            config.tick_rate, config.network_tick_rate()
+        );
+    }
+}
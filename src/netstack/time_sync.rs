@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+use serde::{Serialize, Deserialize};
+use super::{
+    client::Client,
+    connection::{client_connection_state_system, ClientConnectionStarted},
+    server::Server,
+    tick::SimulationTickConfig
+};
+
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct TimeSyncPing {
+    // client real time in seconds when sent
+    pub client_time: f64
+}
+
+#[derive(Event, Serialize, Deserialize, Clone)]
+pub struct TimeSyncPong {
+    pub client_time: f64,
+    pub server_time: f64,
+    pub server_tick: u32
+}
+
+#[derive(Resource, Clone)]
+pub struct TimeSyncConfig {
+    pub ping_interval_seconds: f32,
+    // weight of a new sample, 0.0 - 1.0
+    pub smoothing: f64,
+    // how many ticks other entities are rendered behind latest received state
+    pub interpolation_delay_ticks: f64
+}
+
+impl Default for TimeSyncConfig {
+    fn default() -> Self {
+        Self{
+            ping_interval_seconds: 0.5,
+            smoothing: 0.1,
+            interpolation_delay_ticks: 1.0
+        }
+    }
+}
+
+// client side estimation of server clock
+#[derive(Resource, Default)]
+pub struct ServerClock {
+    synced: bool,
+    rtt: f64,
+    // server time - client time
+    clock_offset: f64,
+    // server tick - client time in ticks
+    tick_offset: f64,
+    tick_delta: f64
+}
+
+impl ServerClock {
+    #[inline]
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    // smoothed round trip time in seconds
+    #[inline]
+    pub fn rtt(&self) -> f64 {
+        self.rtt
+    }
+
+    #[inline]
+    pub fn clock_offset(&self) -> f64 {
+        self.clock_offset
+    }
+
+    #[inline]
+    pub fn tick_delta(&self) -> f64 {
+        self.tick_delta
+    }
+
+    #[inline]
+    pub fn server_tick_at(&self, client_time: f64) -> f64 {
+        if !self.synced {
+            return 0.0;
+        }
+        client_time / self.tick_delta + self.tick_offset
+    }
+
+    fn apply_pong(
+        &mut self, 
+        pong: &TimeSyncPong, 
+        now: f64, 
+        tick_delta: f64, 
+        smoothing: f64
+    ) {
+        let rtt = (now - pong.client_time).max(0.0);
+        let clock_offset = pong.server_time + rtt * 0.5 - now;
+        let tick_offset = pong.server_tick as f64 + rtt * 0.5 / tick_delta - now / tick_delta;
+
+        if !self.synced || self.tick_delta != tick_delta {
+            self.synced = true;
+            self.rtt = rtt;
+            self.clock_offset = clock_offset;
+            self.tick_offset = tick_offset;
+            self.tick_delta = tick_delta;
+            return;
+        }
+
+        self.rtt += (rtt - self.rtt) * smoothing;
+        self.clock_offset += (clock_offset - self.clock_offset) * smoothing;
+        self.tick_offset += (tick_offset - self.tick_offset) * smoothing;
+    }
+}
+
+// server tick the server is running right now, estimated by client
+#[derive(Resource, Default)]
+pub struct EstimatedServerTick(pub u32);
+
+// server tick of the state client is showing for interpolated entities
+#[derive(Resource, Default)]
+pub struct InterpolationTick(pub u32);
+
+#[derive(Resource)]
+struct PingTimer(Timer);
+
+pub struct TimeSyncPlugin;
+
+impl Plugin for TimeSyncPlugin {
+    fn build(&self, app: &mut App) {
+        let ping_interval = app.world
+        .get_resource_or_insert_with(TimeSyncConfig::default)
+        .ping_interval_seconds;
+
+        app.insert_resource(PingTimer(
+            Timer::from_seconds(ping_interval, TimerMode::Repeating)
+        ))
+        .init_resource::<ServerClock>()
+        .init_resource::<EstimatedServerTick>()
+        .init_resource::<InterpolationTick>()
+        .add_client_event::<TimeSyncPing>(ChannelKind::Unreliable)
+        .add_server_event::<TimeSyncPong>(ChannelKind::Unreliable)
+        .add_systems(PreUpdate, (
+            reset_clock_system,
+            receive_pong_system,
+            update_tick_estimation_system
+        )
+            .chain()
+            .after(ClientSet::Receive)
+            .after(client_connection_state_system)
+            .run_if(resource_exists::<Client>)
+        )
+        .add_systems(Update,
+            send_ping_system.run_if(resource_exists::<Client>)
+        )
+        .add_systems(Update,
+            respond_ping_system.run_if(resource_exists::<Server>)
+        );
+    }
+}
+
+fn send_ping_system(
+    mut timer: ResMut<PingTimer>,
+    time: Res<Time<Real>>,
+    mut pings: EventWriter<TimeSyncPing>
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    pings.send(TimeSyncPing{
+        client_time: time.elapsed_seconds_f64()
+    });
+}
+
+fn respond_ping_system(
+    mut pings: EventReader<FromClient<TimeSyncPing>>,
+    mut pongs: EventWriter<ToClients<TimeSyncPong>>,
+    replicon_tick: Res<RepliconTick>,
+    time: Res<Time<Real>>
+) {
+    for FromClient { client_id, event } in pings.read() {
+        pongs.send(ToClients{
+            mode: SendMode::Direct(*client_id),
+            event: TimeSyncPong{
+                client_time: event.client_time,
+                server_time: time.elapsed_seconds_f64(),
+                server_tick: replicon_tick.get()
+            }
+        });
+    }
+}
+
+// samples of the previous connection do not hold for the next one
+fn reset_clock_system(
+    mut started: EventReader<ClientConnectionStarted>,
+    mut clock: ResMut<ServerClock>,
+    mut estimated_tick: ResMut<EstimatedServerTick>,
+    mut interpolation_tick: ResMut<InterpolationTick>
+) {
+    if started.read().last().is_some() {
+        *clock = default();
+        *estimated_tick = default();
+        *interpolation_tick = default();
+    }
+}
+
+fn receive_pong_system(
+    mut pongs: EventReader<TimeSyncPong>,
+    mut clock: ResMut<ServerClock>,
+    config: Res<TimeSyncConfig>,
+    simulation_tick: Option<Res<SimulationTickConfig>>,
+    time: Res<Time<Real>>
+) {
+    // tick is not known until server sends config
+    let Some(simulation_tick) = simulation_tick else {
+        return;
+    };
+
+    let now = time.elapsed_seconds_f64();
+    let tick_delta = simulation_tick.network_tick_delta() as f64;
+    for pong in pongs.read() {
+        clock.apply_pong(pong, now, tick_delta, config.smoothing);
+    }
+}
+
+fn update_tick_estimation_system(
+    clock: Res<ServerClock>,
+    config: Res<TimeSyncConfig>,
+    mut estimated_tick: ResMut<EstimatedServerTick>,
+    mut interpolation_tick: ResMut<InterpolationTick>,
+    time: Res<Time<Real>>
+) {
+    if !clock.is_synced() {
+        return;
+    }
+
+    let server_tick = clock.server_tick_at(time.elapsed_seconds_f64());
+    // latest state received was made half rtt ago
+    let received_tick = server_tick - clock.rtt() * 0.5 / clock.tick_delta();
+    let interpolated_tick = received_tick - config.interpolation_delay_ticks;
+
+    estimated_tick.0 = server_tick.max(0.0) as u32;
+    interpolation_tick.0 = interpolated_tick.max(0.0) as u32;
+}
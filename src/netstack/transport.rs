@@ -0,0 +1,10 @@
+use super::loopback::LoopbackNetwork;
+
+#[derive(Clone, Default)]
+pub enum NetstackTransport {
+    // netcode over udp with addresses in config
+    #[default]
+    Udp,
+    // in-process, addresses and keys in config are not used
+    Loopback(LoopbackNetwork)
+}
@@ -0,0 +1,202 @@
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use bevy_replicon::{prelude::*, server::connected_clients::ConnectedClients};
+use super::{
+    components::{NetworkPlayer, NetworkTranslation2D},
+    resources::PlayerEntityMap,
+    room::{is_visible_in_room, InRoom, Rooms},
+    server::Server
+};
+
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum VisibilitySet {
+    // decides which rooms each client can see
+    Room,
+    // narrows visibility down inside the room
+    Interest,
+    // writes the result to replicon
+    Apply
+}
+
+// visibility changes decided in this frame for each client,
+// applied to replicon only where it differs so that
+// systems can narrow it down without flickering.
+// entities without an entry keep their visibility
+#[derive(Resource, Default)]
+pub struct PendingVisibility(HashMap<ClientId, HashMap<Entity, bool>>);
+
+impl PendingVisibility {
+    #[inline]
+    pub fn set(&mut self, client_id: ClientId, entity: Entity, visible: bool) {
+        self.0.entry(client_id).or_default().insert(entity, visible);
+    }
+
+    #[inline]
+    pub fn get(&self, client_id: &ClientId, entity: &Entity) -> Option<bool> {
+        self.0.get(client_id)?.get(entity).copied()
+    }
+}
+
+// area of interest, server replicates entities with translation
+// only to clients whose player is within the radius.
+// entities without translation are not filtered
+#[derive(Resource, Clone)]
+pub struct InterestConfig {
+    pub radius: f32,
+    // visible entities are kept until radius + hysteresis
+    // so that entities on the border do not respawn every frame
+    pub hysteresis: f32,
+    pub cell_size: f32
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self{
+            radius: 50.0,
+            hysteresis: 5.0,
+            cell_size: 25.0
+        }
+    }
+}
+
+// uniform grid of replicated entities with translation, rebuilt every frame
+#[derive(Resource, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>
+}
+
+impl SpatialGrid {
+    #[inline]
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    fn rebuild(&mut self, cell_size: f32, entities: impl Iterator<Item = (Entity, Vec2)>) {
+        self.cell_size = cell_size.max(f32::EPSILON);
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        for (e, position) in entities {
+            let cell = self.cell(position);
+            self.cells.entry(cell).or_default().push((e, position));
+        }
+        self.cells.retain(|_, v| !v.is_empty());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Entity, Vec2)> {
+        self.cells.values().flatten()
+    }
+
+    pub fn within(&self, center: Vec2, radius: f32)
+    -> impl Iterator<Item = &(Entity, Vec2)> {
+        let min = self.cell(center - Vec2::splat(radius));
+        let max = self.cell(center + Vec2::splat(radius));
+        let radius_squared = radius * radius;
+        (min.x..=max.x)
+        .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+        .filter_map(|cell| self.cells.get(&cell))
+        .flatten()
+        .filter(move |(_, p)| p.distance_squared(center) <= radius_squared)
+    }
+}
+
+pub struct VisibilityPlugin;
+
+impl Plugin for VisibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingVisibility>()
+        .init_resource::<SpatialGrid>()
+        .configure_sets(PostUpdate, (
+            VisibilitySet::Room,
+            VisibilitySet::Interest,
+            VisibilitySet::Apply
+        ).chain().before(ServerSet::Send))
+        .add_systems(PostUpdate, (
+            interest_system
+            .in_set(VisibilitySet::Interest)
+            .run_if(resource_exists::<InterestConfig>),
+            apply_visibility_system
+            .in_set(VisibilitySet::Apply)
+        ).run_if(resource_exists::<Server>));
+    }
+}
+
+// hides what is out of range and shows again what comes back within the room.
+// owners always see their own player.
+// hidden entities are despawned on the client by replicon
+// and spawned again with fresh components when they come back
+fn interest_system(
+    query: Query<(Entity, &NetworkTranslation2D, Option<&NetworkPlayer>, Option<&InRoom>), With<Replication>>,
+    config: Res<InterestConfig>,
+    rooms: Res<Rooms>,
+    player_entities: Res<PlayerEntityMap>,
+    connected_clients: Res<ConnectedClients>,
+    mut grid: ResMut<SpatialGrid>,
+    mut pending: ResMut<PendingVisibility>,
+    mut in_range: Local<HashSet<Entity>>
+) {
+    grid.rebuild(config.cell_size, query.iter().map(|(e, t, ..)| (e, t.0)));
+
+    for client in connected_clients.iter() {
+        let client_id = client.id();
+        // client without player in the world has no viewpoint yet
+        let Some(center) = player_entities.get(&client_id)
+        .and_then(|e| query.get(*e).ok())
+        .map(|(_, t, ..)| t.0) else {
+            continue;
+        };
+
+        in_range.clear();
+        in_range.extend(grid.within(center, config.radius).map(|(e, _)| *e));
+        let outer_radius_squared = (config.radius + config.hysteresis).powi(2);
+        let client_room = rooms.client_room(&client_id);
+
+        let visibility = client.visibility();
+        for (e, position) in grid.iter() {
+            // room only writes what changed, anything else keeps its visibility
+            let decided = pending.get(&client_id, e);
+            if in_range.contains(e) {
+                if decided.is_none() && !visibility.is_visible(*e) {
+                    let in_room = query.get(*e).ok().and_then(|(.., r)| r);
+                    if is_visible_in_room(in_room, client_room) {
+                        pending.set(client_id, *e, true);
+                    }
+                }
+                continue;
+            }
+            if !decided.unwrap_or_else(|| visibility.is_visible(*e)) {
+                continue;
+            }
+            if let Ok((_, _, Some(p), _)) = query.get(*e) {
+                if p.client_id() == client_id {
+                    continue;
+                }
+            }
+            if visibility.is_visible(*e)
+            && position.distance_squared(center) <= outer_radius_squared {
+                continue;
+            }
+            pending.set(client_id, *e, false);
+        }
+    }
+}
+
+fn apply_visibility_system(
+    mut pending: ResMut<PendingVisibility>,
+    mut connected_clients: ResMut<ConnectedClients>
+) {
+    for client in connected_clients.iter_mut() {
+        let Some(entities) = pending.0.get_mut(&client.id()) else {
+            continue;
+        };
+
+        let visibility = client.visibility_mut();
+        for (e, visible) in entities.drain() {
+            if visibility.is_visible(e) != visible {
+                visibility.set_visibility(e, visible);
+            }
+        }
+    }
+    // drops entries of disconnected clients
+    pending.0.clear();
+}
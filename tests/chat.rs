@@ -0,0 +1,105 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::{
+    dev::{
+        chat::{ChatAppExt, ChatLog, ChatRequest, ChatScope, ChatVerdict},
+        team::Team
+    },
+    netstack::loopback::LoopbackNetwork
+};
+use bevy_replicon::core::ClientId;
+
+const SETTLE_FRAMES: usize = 100;
+
+fn mask_darn(text: &str) -> ChatVerdict {
+    if text.contains("darn") {
+        ChatVerdict::Replace(text.replace("darn", "****"))
+    } else {
+        ChatVerdict::Allow
+    }
+}
+
+fn reject_spam(text: &str) -> ChatVerdict {
+    if text.contains("spam") {
+        ChatVerdict::Reject
+    } else {
+        ChatVerdict::Allow
+    }
+}
+
+fn texts(client: &App) -> Vec<(String, String)> {
+    client.world.resource::<ChatLog>().iter()
+    .map(|m| (m.sender_name.clone(), m.text.clone()))
+    .collect()
+}
+
+fn say(server: &mut App, clients: &mut [App], sender: usize, scope: ChatScope, text: &str) {
+    clients[sender].world.send_event(ChatRequest{
+        scope,
+        text: text.to_string()
+    });
+    common::update(server, clients, 20);
+}
+
+#[test]
+fn chat_is_scoped_and_filtered() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.add_chat_filter(mask_darn)
+    .add_chat_filter(reject_spam);
+
+    let mut clients = vec![];
+    for (i, (name, team)) in [("alice", Team::Red), ("bob", Team::Blue), ("carol", Team::Red)]
+    .into_iter().enumerate() {
+        let mut client = common::game_client_app(&network, i as u64 + 1);
+        common::set_display_name(&mut client, name);
+        common::request_team(&mut client, Some(team));
+        clients.push(client);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    say(&mut server, &mut clients, 0, ChatScope::All, " hello darn ");
+    let hello = ("alice".to_string(), "hello ****".to_string());
+    for client in clients.iter() {
+        assert_eq!(texts(client), vec![hello.clone()]);
+    }
+
+    say(&mut server, &mut clients, 2, ChatScope::Team, "push left");
+    let team = ("carol".to_string(), "push left".to_string());
+    assert_eq!(texts(&clients[0]), vec![hello.clone(), team.clone()]);
+    assert_eq!(texts(&clients[1]), vec![hello.clone()]);
+
+    say(&mut server, &mut clients, 1, ChatScope::Whisper(ClientId::new(1)), "gg");
+    let whisper = ("bob".to_string(), "gg".to_string());
+    assert_eq!(texts(&clients[0]), vec![hello.clone(), team.clone(), whisper.clone()]);
+    assert_eq!(texts(&clients[1]), vec![hello.clone(), whisper.clone()]);
+    assert_eq!(texts(&clients[2]), vec![hello.clone(), team.clone()]);
+
+    // rejected ones are not delivered
+    say(&mut server, &mut clients, 0, ChatScope::All, "spam");
+    say(&mut server, &mut clients, 0, ChatScope::All, &"a".repeat(201));
+    say(&mut server, &mut clients, 0, ChatScope::All, "   ");
+    say(&mut server, &mut clients, 0, ChatScope::Whisper(ClientId::new(99)), "hi");
+    assert_eq!(texts(&clients[1]), vec![hello, whisper]);
+}
+
+#[test]
+fn sender_names_are_sanitized() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = vec![];
+    for (i, name) in ["eve\n[server]", "\t"].into_iter().enumerate() {
+        let mut client = common::game_client_app(&network, i as u64 + 1);
+        common::set_display_name(&mut client, name);
+        clients.push(client);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    say(&mut server, &mut clients, 0, ChatScope::All, "hi");
+    say(&mut server, &mut clients, 1, ChatScope::All, "hey");
+    assert_eq!(texts(&clients[0]), vec![
+        ("eve[server]".to_string(), "hi".to_string()),
+        ("player 2".to_string(), "hey".to_string())
+    ]);
+}
@@ -0,0 +1,173 @@
+#![allow(dead_code)]
+
+use std::{net::{IpAddr, Ipv4Addr}, time::Duration};
+use bevy::{ecs::system::RunSystemOnce, prelude::*, time::TimeUpdateStrategy, utils::Uuid};
+use bevy_replicon::core::ClientId;
+use bevy_net_dev::{
+    dev::{
+        config::*,
+        game::{direction_to_yaw, GamePlugin},
+        team::{Team, TEAM_USER_DATA_INDEX}
+    },
+    netstack::{
+        client::{setup_client, ClientConfig, ClientNetstackPlugin},
+        components::{NetworkPlayer, NetworkTranslation2D, NetworkYaw},
+        loopback::LoopbackNetwork,
+        resources::PlayerEntityMap,
+        room::{RoomId, ROOM_USER_DATA_RANGE},
+        server::{ServerConfig, ServerNetstackPlugin, DISPLAY_NAME_USER_DATA_RANGE},
+        transport::NetstackTransport
+    }
+};
+
+// one frame of every app, shorter than simulation tick
+pub const TEST_FRAME_DELTA: Duration = Duration::from_millis(10);
+
+pub fn server_app(network: &LoopbackNetwork) -> App {
+    let mut app = App::new();
+    app.insert_resource(ServerConfig{
+        simulation_tick_rate: DEV_SIMULATION_TICK_RATE,
+        replication_interval: DEV_REPLICATION_INTERVAL,
+        listen_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        listen_port: 0,
+        protocol_id: 0,
+        private_key: [0; 32],
+        max_clients: DEV_SERVER_MAX_CLIENTS,
+        transport: NetstackTransport::Loopback(network.clone()),
+        replication_budget: default()
+    })
+    .insert_resource(TimeUpdateStrategy::ManualDuration(TEST_FRAME_DELTA))
+    .add_plugins((
+        MinimalPlugins,
+        ServerNetstackPlugin
+    ));
+    app
+}
+
+pub fn client_config(network: &LoopbackNetwork, client_id: u64) -> ClientConfig {
+    ClientConfig{
+        client_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        server_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+        server_port: 0,
+        timeout_seconds: DEV_CLIENT_TIME_OUT_SEC,
+        client_id,
+        protocol_id: 0,
+        private_key: [0; 32],
+        user_data: user_data(Uuid::from_u128(client_id as u128)),
+        token_expire_seconds: DEV_TOKEN_EXPIRE_SEC,
+        transport: NetstackTransport::Loopback(network.clone())
+    }
+}
+
+pub fn client_app(network: &LoopbackNetwork, client_id: u64) -> App {
+    let mut app = App::new();
+    app.insert_resource(client_config(network, client_id))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(TEST_FRAME_DELTA))
+    .add_plugins((
+        MinimalPlugins,
+        ClientNetstackPlugin
+    ))
+    .add_systems(Startup, setup_client);
+    app
+}
+
+pub fn game_server_app(network: &LoopbackNetwork) -> App {
+    let mut app = server_app(network);
+    app.add_plugins(GamePlugin);
+    app
+}
+
+pub fn game_client_app(network: &LoopbackNetwork, client_id: u64) -> App {
+    let mut app = client_app(network, client_id);
+    app.add_plugins(GamePlugin);
+    app
+}
+
+pub fn user_data(uuid: Uuid) -> [u8; 256] {
+    let mut user_data = [0u8; 256];
+    user_data[0..16].copy_from_slice(uuid.as_bytes());
+    user_data
+}
+
+// same app connects again after it was disconnected
+pub fn reconnect(client: &mut App, network: &LoopbackNetwork, client_id: u64) {
+    client.insert_resource(client_config(network, client_id));
+    client.world.run_system_once(setup_client);
+}
+
+// server goes first so that clients can receive in the same frame
+pub fn update(server: &mut App, clients: &mut [App], frames: usize) {
+    for _ in 0..frames {
+        server.update();
+        for client in clients.iter_mut() {
+            client.update();
+        }
+    }
+}
+
+// before the first update so that it is sent on connect
+pub fn request_room(client: &mut App, room: RoomId) {
+    let mut config = client.world.resource_mut::<ClientConfig>();
+    config.user_data[ROOM_USER_DATA_RANGE].copy_from_slice(&room.0.to_le_bytes());
+}
+
+// before the first update so that it is sent on connect
+pub fn request_team(client: &mut App, team: Option<Team>) {
+    let mut config = client.world.resource_mut::<ClientConfig>();
+    config.user_data[TEAM_USER_DATA_INDEX] = Team::to_user_data(team);
+}
+
+// before the first update so that it is sent on connect
+pub fn set_display_name(client: &mut App, name: &str) {
+    let mut config = client.world.resource_mut::<ClientConfig>();
+    let range = &mut config.user_data[DISPLAY_NAME_USER_DATA_RANGE];
+    range.fill(0);
+    range[..name.len()].copy_from_slice(name.as_bytes());
+}
+
+pub fn player_entity(server: &App, client_id: u64) -> Entity {
+    *server.world.resource::<PlayerEntityMap>()
+    .get(&ClientId::new(client_id))
+    .expect("player should be mapped")
+}
+
+pub fn server_translation(server: &App, client_id: u64) -> Vec2 {
+    server.world.get::<NetworkTranslation2D>(player_entity(server, client_id))
+    .expect("player should have translation")
+    .0
+}
+
+// moves the entity on server without inputs
+pub fn set_translation(server: &mut App, e: Entity, translation: Vec2) {
+    server.world.get_mut::<NetworkTranslation2D>(e)
+    .expect("entity should have translation")
+    .0 = translation;
+}
+
+// replicated translation of a player seen by a client
+pub fn client_translation(client: &mut App, client_id: u64) -> Option<Vec2> {
+    let mut query = client.world.query::<(&NetworkPlayer, &NetworkTranslation2D)>();
+    query.iter(&client.world)
+    .find(|(p, _)| p.client_id().get() == client_id)
+    .map(|(_, t)| t.0)
+}
+
+// client ids of players replicated to a client, sorted
+pub fn visible_players(client: &mut App) -> Vec<u64> {
+    let mut query = client.world.query::<&NetworkPlayer>();
+    let mut players = query.iter(&client.world)
+    .map(|p| p.client_id().get())
+    .collect::<Vec<_>>();
+    players.sort();
+    players
+}
+
+// turns the player on server towards the point,
+// shots aim with the yaw shooter saw so that update before firing
+pub fn aim(server: &mut App, client_id: u64, target: Vec2) {
+    let e = player_entity(server, client_id);
+    let from = server_translation(server, client_id);
+    server.world.get_mut::<NetworkYaw>(e)
+    .expect("player should have yaw")
+    .0 = direction_to_yaw(target - from);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::{
+    dev::match_state::{MatchConfig, MATCH_FULL_REASON},
+    netstack::{
+        admin::kick_client,
+        connection::{ClientConnectionState, ClientDisconnected, DisconnectReason},
+        loopback::LoopbackNetwork
+    }
+};
+use bevy_replicon_renet::renet::RenetClient;
+
+const SETTLE_FRAMES: usize = 50;
+
+fn state(client: &App) -> ClientConnectionState {
+    *client.world.resource::<State<ClientConnectionState>>().get()
+}
+
+// events are kept for two frames only
+fn update_collect(server: &mut App, clients: &mut [App], frames: usize) -> Vec<Vec<ClientDisconnected>> {
+    let mut readers = clients.iter().map(|_| ManualEventReader::<ClientDisconnected>::default())
+    .collect::<Vec<_>>();
+    let mut received = clients.iter().map(|_| vec![]).collect::<Vec<_>>();
+    for _ in 0..frames {
+        common::update(server, clients, 1);
+        for (i, client) in clients.iter().enumerate() {
+            let events = client.world.resource::<Events<ClientDisconnected>>();
+            received[i].extend(readers[i].read(events).cloned());
+        }
+    }
+    received
+}
+
+#[test]
+fn client_connects_and_disconnects() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+    assert_eq!(state(&clients[0]), ClientConnectionState::Disconnected);
+
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    assert_eq!(state(&clients[0]), ClientConnectionState::Connected);
+
+    clients[0].world.resource_mut::<RenetClient>().disconnect();
+    let received = update_collect(&mut server, &mut clients, 10);
+    assert_eq!(state(&clients[0]), ClientConnectionState::Disconnected);
+    assert_eq!(received[0].len(), 1);
+    assert!(!received[0][0].rejected);
+    assert!(matches!(received[0][0].reason, DisconnectReason::Transport(_)));
+}
+
+#[test]
+fn kicked_client_is_disconnected_with_reason() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    kick_client(&mut server.world, 1, "afk".to_string());
+    let received = update_collect(&mut server, &mut clients, SETTLE_FRAMES);
+    assert_eq!(state(&clients[0]), ClientConnectionState::Disconnected);
+    assert_eq!(received[0].len(), 1);
+    assert!(!received[0][0].rejected);
+    assert_eq!(received[0][0].reason, DisconnectReason::Server("afk".to_string()));
+}
+
+#[test]
+fn client_over_capacity_is_rejected() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.insert_resource(MatchConfig{
+        min_players: 2,
+        max_players: 1,
+        countdown_seconds: 0.5,
+        match_seconds: 1.0,
+        post_match_seconds: 0.5
+    });
+    let mut clients = vec![common::game_client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    clients.push(common::game_client_app(&network, 2));
+    let received = update_collect(&mut server, &mut clients, SETTLE_FRAMES);
+    assert_eq!(state(&clients[0]), ClientConnectionState::Connected);
+    assert_eq!(state(&clients[1]), ClientConnectionState::Rejected);
+    assert!(received[0].is_empty());
+    assert_eq!(received[1].len(), 1);
+    assert!(received[1][0].rejected);
+    assert_eq!(received[1][0].reason, DisconnectReason::Server(MATCH_FULL_REASON.to_string()));
+}
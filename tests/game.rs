@@ -0,0 +1,264 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::{
+    dev::game::{ActionEvent, LagCompensatedFireEvent},
+    netstack::{
+        components::{NetworkPlayer, NetworkTranslation2D},
+        conditioner::{LinkConditionerConfig, LinkConditions},
+        events::NetworkMovement2DEvent,
+        input::{NetworkInputAck, UnackedInputs},
+        loopback::LoopbackNetwork
+    }
+};
+use bevy_replicon::core::ClientId;
+use bevy_replicon_renet::renet::RenetClient;
+use bevy_replicon_snap::prelude::*;
+
+// frames enough for connection, spawn replication and clock sync
+const SETTLE_FRAMES: usize = 100;
+
+fn send_action(client: &mut App, action: ActionEvent) {
+    client.world.send_event(action);
+}
+
+#[test]
+fn players_spawn_and_replicate() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2)
+    ];
+
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    common::player_entity(&server, 1);
+    common::player_entity(&server, 2);
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        let own_id = i as u64 + 1;
+        let mut players = client.world.query::<&NetworkPlayer>();
+        assert_eq!(players.iter(&client.world).count(), 2);
+
+        let mut owned = client.world.query_filtered::<&NetworkPlayer, With<OwnerControlling>>();
+        let owned = owned.iter(&client.world).collect::<Vec<_>>();
+        assert_eq!(owned.len(), 1);
+        assert_eq!(owned[0].client_id().get(), own_id);
+    }
+}
+
+#[test]
+fn movement_is_simulated_and_replicated() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2)
+    ];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    let start = common::server_translation(&server, 1);
+    let other_start = common::server_translation(&server, 2);
+
+    for _ in 0..30 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let moved = common::server_translation(&server, 1);
+    assert!(moved.x > start.x, "server translation: {moved} from: {start}");
+    assert_eq!(moved.y, start.y);
+    // other player has not moved
+    assert_eq!(common::server_translation(&server, 2), other_start);
+
+    for client in clients.iter_mut() {
+        assert_eq!(common::client_translation(client, 1), Some(moved));
+    }
+}
+
+#[test]
+fn inputs_are_acked() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [common::game_client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    for _ in 0..10 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::Y,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let e = common::player_entity(&server, 1);
+    let server_ack = server.world.get::<NetworkInputAck<NetworkMovement2DEvent>>(e)
+    .and_then(|a| a.sequence())
+    .expect("server should ack inputs");
+
+    let unacked = clients[0].world.resource::<UnackedInputs<NetworkMovement2DEvent>>();
+    assert_eq!(unacked.acked_sequence(), Some(server_ack));
+    assert_eq!(unacked.len(), 0);
+}
+
+#[test]
+fn lost_inputs_are_resent_after_the_player_stops() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [common::game_client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    let start = common::server_translation(&server, 1);
+
+    // the only packets carrying these inputs are lost
+    network.link_conditions().set(LinkConditions{
+        upstream: LinkConditionerConfig{
+            loss: 1.0,
+            ..default()
+        },
+        downstream: default()
+    });
+    for _ in 0..5 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    network.link_conditions().set(LinkConditions::default());
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let moved = common::server_translation(&server, 1);
+    assert!(moved.x > start.x, "server translation: {moved} from: {start}");
+    let unacked = clients[0].world.resource::<UnackedInputs<NetworkMovement2DEvent>>();
+    assert!(unacked.acked_sequence().is_some());
+    assert_eq!(unacked.len(), 0);
+}
+
+#[test]
+fn inputs_are_acked_after_reconnect() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [common::game_client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    // previous connection gets further in sequence than the next one
+    for _ in 0..50 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    clients[0].world.resource_mut::<RenetClient>().disconnect();
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    common::reconnect(&mut clients[0], &network, 1);
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    for _ in 0..5 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let e = common::player_entity(&server, 1);
+    let server_ack = server.world.get::<NetworkInputAck<NetworkMovement2DEvent>>(e)
+    .and_then(|a| a.sequence())
+    .expect("server should ack inputs of the new connection");
+
+    let unacked = clients[0].world.resource::<UnackedInputs<NetworkMovement2DEvent>>();
+    assert_eq!(unacked.acked_sequence(), Some(server_ack));
+    assert_eq!(unacked.len(), 0);
+}
+
+#[test]
+fn server_snapshots_follow_ticks() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [common::game_client_app(&network, 1)];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    for _ in 0..30 {
+        send_action(&mut clients[0], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+
+    let e = common::player_entity(&server, 1);
+    let buffer = server.world.get::<ComponentSnapshotBuffer<NetworkTranslation2D>>(e)
+    .expect("player should have snapshots");
+    let ticks = buffer.iter().map(|s| s.tick()).collect::<Vec<_>>();
+    assert!(ticks.len() > 2, "snapshots: {ticks:?}");
+    assert!(ticks.windows(2).all(|w| w[0] <= w[1]), "snapshots: {ticks:?}");
+    let latest = buffer.iter().last().expect("snapshots should not be empty");
+    assert_eq!(latest.component().0, common::server_translation(&server, 1));
+}
+
+#[test]
+fn fire_is_lag_compensated() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2)
+    ];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    for _ in 0..30 {
+        send_action(&mut clients[1], ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+    }
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let target = common::player_entity(&server, 2);
+    let standing = common::server_translation(&server, 2);
+
+    let mut reader = ManualEventReader::<LagCompensatedFireEvent>::default();
+    // skip what happened before fire
+    reader.clear(server.world.resource::<Events<LagCompensatedFireEvent>>());
+
+    send_action(&mut clients[0], ActionEvent{
+        movement_vec: Vec2::ZERO,
+        is_fire: true
+    });
+    // target starts to move when the shot is fired,
+    // the shooter still sees it standing for the interpolation delay
+    let mut fired = vec![];
+    for _ in 0..SETTLE_FRAMES {
+        send_action(&mut clients[1], ActionEvent{
+            movement_vec: Vec2::Y,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+        let events = server.world.resource::<Events<LagCompensatedFireEvent>>();
+        fired.extend(reader.read(events).cloned());
+    }
+
+    let hit = fired.iter()
+    .find(|f| f.target == target)
+    .expect("fire should look up the other player");
+    assert_eq!(hit.shooter, ClientId::new(1));
+
+    let moved = common::server_translation(&server, 2);
+    // screen up is -y in network space
+    assert!(moved.y < standing.y, "server translation: {moved} from: {standing}");
+    // looked up state is the past one the shooter saw, not the current one
+    assert_eq!(hit.translation, standing);
+    let buffer = server.world.get::<ComponentSnapshotBuffer<NetworkTranslation2D>>(target)
+    .expect("player should have snapshots");
+    assert!(hit.translation_tick < buffer.latest_snapshot_tick());
+    let snapshot = buffer.iter().rfind(|s| s.tick() <= hit.translation_tick)
+    .expect("fire tick should be in history");
+    assert_eq!(snapshot.component().0, standing);
+}
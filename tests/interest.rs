@@ -0,0 +1,55 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::netstack::{
+    components::{NetworkPlayer, NetworkTranslation2D},
+    loopback::LoopbackNetwork,
+    visibility::InterestConfig
+};
+use bevy_replicon_snap::prelude::*;
+
+const SETTLE_FRAMES: usize = 100;
+
+#[test]
+fn out_of_range_players_are_despawned_and_respawned() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.insert_resource(InterestConfig{
+        radius: 20.0,
+        hysteresis: 2.0,
+        cell_size: 10.0
+    });
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2)
+    ];
+
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1, 2]);
+    assert_eq!(common::visible_players(&mut clients[1]), vec![1, 2]);
+
+    // inside hysteresis players are kept
+    common::set_translation(&mut server, common::player_entity(&server, 1), Vec2::ZERO);
+    common::set_translation(&mut server, common::player_entity(&server, 2), Vec2::new(21.0, 0.0));
+    common::update(&mut server, &mut clients, 20);
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1, 2]);
+
+    // owner always sees its own player
+    common::set_translation(&mut server, common::player_entity(&server, 2), Vec2::new(100.0, 0.0));
+    common::update(&mut server, &mut clients, 20);
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1]);
+    assert_eq!(common::visible_players(&mut clients[1]), vec![2]);
+
+    common::set_translation(&mut server, common::player_entity(&server, 2), Vec2::new(5.0, 0.0));
+    common::update(&mut server, &mut clients, 20);
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1, 2]);
+
+    // respawned player starts from a fresh buffer at the new position
+    let mut query = clients[0].world.query::<(
+        &NetworkPlayer, &ComponentSnapshotBuffer<NetworkTranslation2D>
+    )>();
+    let (_, buffer) = query.iter(&clients[0].world)
+    .find(|(p, _)| p.client_id().get() == 2)
+    .expect("player should be respawned with snapshot buffer");
+    assert!(buffer.iter().all(|s| s.component().0.distance(Vec2::new(5.0, 0.0)) < 1.0));
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use bevy::{app::AppExit, ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::netstack::{
+    conditioner::{LinkConditionerConfig, LinkConditions},
+    connection::ClientConnectionState,
+    lifecycle::{HealthHandle, ShutdownConfig, ShutdownHandle},
+    loopback::LoopbackNetwork,
+    resources::PlayerEntityMap,
+    stats::NetworkStats
+};
+use bevy_replicon::core::ClientId;
+use bevy_replicon_renet::renet::RenetClient;
+
+#[test]
+fn client_connects_and_disconnects() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+
+    common::update(&mut server, &mut clients, 10);
+    assert!(clients[0].world.resource::<RenetClient>().is_connected());
+    assert!(server.world.resource::<PlayerEntityMap>().get(&ClientId::new(1)).is_some());
+
+    clients[0].world.resource_mut::<RenetClient>().disconnect();
+    common::update(&mut server, &mut clients, 10);
+    assert!(server.world.resource::<PlayerEntityMap>().get(&ClientId::new(1)).is_none());
+}
+
+#[test]
+fn clients_are_isolated_by_network() {
+    let network = LoopbackNetwork::default();
+    let other_network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [
+        common::client_app(&network, 1),
+        common::client_app(&other_network, 2)
+    ];
+
+    common::update(&mut server, &mut clients, 10);
+    let players = server.world.resource::<PlayerEntityMap>();
+    assert!(players.get(&ClientId::new(1)).is_some());
+    assert!(players.get(&ClientId::new(2)).is_none());
+    assert!(!clients[1].world.resource::<RenetClient>().is_connected());
+}
+
+#[test]
+fn client_connects_over_lossy_link() {
+    let network = LoopbackNetwork::with_seed(0);
+    network.link_conditions().set(LinkConditions{
+        upstream: LinkConditionerConfig{
+            latency_ms: 30.0,
+            jitter_ms: 10.0,
+            loss: 0.1,
+            ..default()
+        },
+        downstream: LinkConditionerConfig{
+            latency_ms: 30.0,
+            jitter_ms: 10.0,
+            loss: 0.1,
+            duplicate: 0.05,
+            reorder: 0.05,
+            reorder_delay_ms: 20.0
+        }
+    });
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+
+    common::update(&mut server, &mut clients, 50);
+    assert!(clients[0].world.resource::<RenetClient>().is_connected());
+    assert!(server.world.resource::<PlayerEntityMap>().get(&ClientId::new(1)).is_some());
+}
+
+#[test]
+fn network_stats_are_collected_on_both_sides() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+
+    common::update(&mut server, &mut clients, 50);
+    let server_stats = server.world.resource::<NetworkStats>();
+    let connection = server_stats.get(&ClientId::new(1)).expect("server should sample client");
+    assert!(connection.summary().samples > 0);
+
+    let client_stats = clients[0].world.resource::<NetworkStats>();
+    assert!(client_stats.get(&ClientId::SERVER).is_some());
+
+    clients[0].world.resource_mut::<RenetClient>().disconnect();
+    common::update(&mut server, &mut clients, 10);
+    assert!(server.world.resource::<NetworkStats>().get(&ClientId::new(1)).is_none());
+}
+
+#[test]
+fn shutdown_disconnects_clients_and_exits() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [common::client_app(&network, 1)];
+
+    common::update(&mut server, &mut clients, 10);
+    assert!(server.world.resource::<HealthHandle>().is_ready());
+
+    server.world.resource::<ShutdownHandle>().request();
+    let mut exits = ManualEventReader::<AppExit>::default();
+    let mut exited = false;
+    for _ in 0..100 {
+        common::update(&mut server, &mut clients, 1);
+        let events = server.world.resource::<Events<AppExit>>();
+        exited |= exits.read(events).next().is_some();
+    }
+
+    let health = server.world.resource::<HealthHandle>();
+    assert!(!health.is_ready());
+    assert!(health.is_shutting_down());
+    assert!(!clients[0].world.resource::<RenetClient>().is_connected());
+    assert!(exited);
+}
+
+#[test]
+fn clients_connecting_while_shutting_down_are_rejected() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    server.insert_resource(ShutdownConfig{
+        notice_seconds: 0.5,
+        ..default()
+    });
+    let mut clients = vec![common::client_app(&network, 1)];
+    common::update(&mut server, &mut clients, 10);
+
+    server.world.resource::<ShutdownHandle>().request();
+    common::update(&mut server, &mut clients, 1);
+    clients.push(common::client_app(&network, 2));
+    common::update(&mut server, &mut clients, 10);
+    // connected at transport level but the player is never spawned
+    assert!(server.world.resource::<PlayerEntityMap>().get(&ClientId::new(2)).is_none());
+
+    common::update(&mut server, &mut clients, 100);
+    let state = clients[1].world.resource::<State<ClientConnectionState>>();
+    assert_eq!(*state.get(), ClientConnectionState::Rejected);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::{
+    dev::{
+        game::ActionEvent,
+        match_state::{MatchConfig, MatchPhase, MatchState}
+    },
+    netstack::{
+        components::NetworkPlayer,
+        loopback::LoopbackNetwork
+    }
+};
+use bevy_replicon::server::connected_clients::ConnectedClients;
+
+const SETTLE_FRAMES: usize = 100;
+
+fn phase(app: &mut App) -> Option<MatchPhase> {
+    let mut query = app.world.query::<&MatchState>();
+    query.get_single(&app.world).ok().map(|s| s.phase)
+}
+
+fn move_for(server: &mut App, clients: &mut [App], frames: usize) {
+    for _ in 0..frames {
+        clients[0].world.send_event(ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(server, clients, 1);
+    }
+}
+
+#[test]
+fn match_goes_through_phases() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.insert_resource(MatchConfig{
+        min_players: 2,
+        max_players: 2,
+        countdown_seconds: 0.5,
+        match_seconds: 1.0,
+        post_match_seconds: 0.5
+    });
+    let mut clients = vec![common::game_client_app(&network, 1)];
+
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    assert_eq!(phase(&mut server), Some(MatchPhase::Waiting));
+    assert_eq!(phase(&mut clients[0]), Some(MatchPhase::Waiting));
+
+    // inputs are ignored until the match starts
+    let before = common::server_translation(&server, 1);
+    move_for(&mut server, &mut clients, 20);
+    assert_eq!(common::server_translation(&server, 1), before);
+
+    clients.push(common::game_client_app(&network, 2));
+    common::update(&mut server, &mut clients, 30);
+    assert_eq!(phase(&mut server), Some(MatchPhase::Countdown));
+    assert_eq!(phase(&mut clients[1]), Some(MatchPhase::Countdown));
+
+    // over capacity
+    clients.push(common::game_client_app(&network, 3));
+    common::update(&mut server, &mut clients, 40);
+    assert_eq!(server.world.resource::<ConnectedClients>().len(), 2);
+    let mut players = server.world.query::<&NetworkPlayer>();
+    assert_eq!(players.iter(&server.world).count(), 2);
+    clients.pop();
+
+    assert_eq!(phase(&mut server), Some(MatchPhase::InProgress));
+    assert_eq!(phase(&mut clients[0]), Some(MatchPhase::InProgress));
+    let before = common::server_translation(&server, 1);
+    move_for(&mut server, &mut clients, 20);
+    assert!(common::server_translation(&server, 1).x > before.x);
+
+    common::update(&mut server, &mut clients, 100);
+    assert_eq!(phase(&mut server), Some(MatchPhase::PostMatch));
+    assert_eq!(phase(&mut clients[0]), Some(MatchPhase::PostMatch));
+
+    // enough players start the next countdown right away
+    common::update(&mut server, &mut clients, 60);
+    assert_eq!(phase(&mut server), Some(MatchPhase::Countdown));
+}
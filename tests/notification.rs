@@ -0,0 +1,100 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::{
+    dev::{
+        match_state::{MatchConfig, MatchNotice, MatchPhase},
+        team::Team
+    },
+    netstack::{
+        admin::kick_client,
+        lifecycle::DisconnectNotice,
+        loopback::LoopbackNetwork,
+        notification::{NotificationAppExt, Notify, Recipients}
+    }
+};
+use bevy_replicon::{core::ClientId, prelude::*};
+use serde::{Deserialize, Serialize};
+
+const SETTLE_FRAMES: usize = 100;
+
+#[derive(Event, Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct TestNotice(u32);
+
+// events are kept for two frames only
+fn collect<E: Event + Clone>(server: &mut App, clients: &mut [App], frames: usize) -> Vec<Vec<E>> {
+    let mut readers = clients.iter().map(|_| ManualEventReader::<E>::default()).collect::<Vec<_>>();
+    let mut received = clients.iter().map(|_| vec![]).collect::<Vec<_>>();
+    for _ in 0..frames {
+        common::update(server, clients, 1);
+        for (i, client) in clients.iter().enumerate() {
+            let events = client.world.resource::<Events<E>>();
+            received[i].extend(readers[i].read(events).cloned());
+        }
+    }
+    received
+}
+
+fn connect(network: &LoopbackNetwork, server: &mut App, teams: &[Team]) -> Vec<App> {
+    let mut clients = vec![];
+    for (i, team) in teams.iter().enumerate() {
+        let mut client = common::game_client_app(network, i as u64 + 1);
+        client.add_notification::<TestNotice>(ChannelKind::Ordered);
+        common::request_team(&mut client, Some(*team));
+        clients.push(client);
+    }
+    common::update(server, &mut clients, SETTLE_FRAMES);
+    clients
+}
+
+#[test]
+fn notifications_reach_recipients() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.add_notification::<TestNotice>(ChannelKind::Ordered);
+    let mut clients = connect(&network, &mut server, &[Team::Red, Team::Blue, Team::Red]);
+
+    server.world.send_event(Notify::new(Recipients::All, TestNotice(0)));
+    server.world.send_event(Notify::new(Recipients::One(ClientId::new(2)), TestNotice(1)));
+    server.world.send_event(Notify::new(Recipients::Group(Team::Red.notification_group()), TestNotice(2)));
+    server.world.send_event(Notify::new(Recipients::AllExcept(ClientId::new(1)), TestNotice(3)));
+    let received = collect::<TestNotice>(&mut server, &mut clients, 20);
+
+    assert_eq!(received[0], vec![TestNotice(0), TestNotice(2)]);
+    assert_eq!(received[1], vec![TestNotice(0), TestNotice(1), TestNotice(3)]);
+    assert_eq!(received[2], vec![TestNotice(0), TestNotice(2), TestNotice(3)]);
+}
+
+#[test]
+fn kicked_client_receives_reason() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.add_notification::<TestNotice>(ChannelKind::Ordered);
+    let mut clients = connect(&network, &mut server, &[Team::Red, Team::Blue]);
+
+    kick_client(&mut server.world, 2, "afk".to_string());
+    let received = collect::<DisconnectNotice>(&mut server, &mut clients, 20);
+    assert!(received[0].is_empty());
+    assert_eq!(received[1].iter().map(|n| n.reason.as_str()).collect::<Vec<_>>(), vec!["afk"]);
+}
+
+#[test]
+fn match_phases_are_notified() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.add_notification::<TestNotice>(ChannelKind::Ordered)
+    .insert_resource(MatchConfig{
+        min_players: 1,
+        max_players: 0,
+        countdown_seconds: 0.5,
+        match_seconds: 10.0,
+        post_match_seconds: 1.0
+    });
+    let mut clients = vec![common::game_client_app(&network, 1)];
+    clients[0].add_notification::<TestNotice>(ChannelKind::Ordered);
+
+    let received = collect::<MatchNotice>(&mut server, &mut clients, 2 * SETTLE_FRAMES);
+    let phases = received[0].iter().map(|n| (n.phase, n.seconds)).collect::<Vec<_>>();
+    assert!(phases.contains(&(MatchPhase::Countdown, Some(0.5))), "notices: {phases:?}");
+    assert!(phases.contains(&(MatchPhase::InProgress, Some(10.0))), "notices: {phases:?}");
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::netstack::{
+    components::NetworkTranslation2D,
+    loopback::LoopbackNetwork,
+    priority::{ReplicationBudget, ReplicationScheduler}
+};
+use bevy_replicon::{core::replicon_tick::RepliconTick, prelude::*};
+
+const SETTLE_FRAMES: usize = 100;
+
+fn replicated(client: &mut App, translation: Vec2) -> bool {
+    let mut query = client.world.query::<&NetworkTranslation2D>();
+    query.iter(&client.world).any(|t| t.0 == translation)
+}
+
+#[test]
+fn starved_entities_are_replicated_within_budget() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2),
+        common::game_client_app(&network, 3)
+    ];
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    // every update is larger than this, so one entity goes per network tick
+    server.world.resource_mut::<ReplicationBudget>().bytes_per_client = 1;
+    let targets = [(1, Vec2::new(1.0, 0.0)), (2, Vec2::new(0.0, 2.0)), (3, Vec2::new(3.0, 3.0))];
+    for (client_id, translation) in targets {
+        let e = common::player_entity(&server, client_id);
+        common::set_translation(&mut server, e, translation);
+    }
+
+    let mut deferred = 0;
+    for _ in 0..40 {
+        common::update(&mut server, &mut clients, 1);
+        deferred = deferred.max(server.world.resource::<ReplicationScheduler>().deferred_len());
+    }
+    assert!(deferred > 0, "updates should have been deferred");
+    assert_eq!(server.world.resource::<ReplicationScheduler>().pending_len(), 0);
+
+    for client in clients.iter_mut() {
+        for (client_id, translation) in targets {
+            assert_eq!(common::client_translation(client, client_id), Some(translation));
+        }
+    }
+}
+
+#[test]
+fn owner_and_nearby_updates_are_sent_before_far_ones() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [common::game_client_app(&network, 1)];
+    let near = server.world.spawn((Replication, NetworkTranslation2D(Vec2::new(10.0, 0.0)))).id();
+    let far = server.world.spawn((Replication, NetworkTranslation2D(Vec2::new(0.0, 40.0)))).id();
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    let owner = common::player_entity(&server, 1);
+    common::set_translation(&mut server, owner, Vec2::ZERO);
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    // every update is larger than this, so one entity goes per network tick
+    server.world.resource_mut::<ReplicationBudget>().bytes_per_client = 1;
+    let targets = [
+        (owner, Vec2::new(0.5, 0.0)),
+        (near, Vec2::new(10.5, 0.0)),
+        (far, Vec2::new(0.0, 40.5))
+    ];
+    for (e, translation) in targets {
+        common::set_translation(&mut server, e, translation);
+    }
+
+    // network tick on which each update reached the client
+    let mut arrived = [None; 3];
+    for _ in 0..40 {
+        common::update(&mut server, &mut clients, 1);
+        let tick = *server.world.resource::<RepliconTick>();
+        for (i, (_, translation)) in targets.iter().enumerate() {
+            if arrived[i].is_none() && replicated(&mut clients[0], *translation) {
+                arrived[i] = Some(tick);
+            }
+        }
+    }
+    let [Some(owner_tick), Some(near_tick), Some(far_tick)] = arrived else {
+        panic!("every update should arrive: {arrived:?}");
+    };
+    // deferred ones were withheld for at least one network tick
+    assert!(owner_tick < near_tick, "arrived: {arrived:?}");
+    assert!(near_tick < far_tick, "arrived: {arrived:?}");
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::netstack::{
+    client_event::AcceptedFromClient,
+    connection::{ClientConnectionState, ClientDisconnected, DisconnectReason},
+    loopback::LoopbackNetwork,
+    rate_limit::{
+        ClientEventRateLimitAppExt, ClientEventRateLimiter, RateLimit, RateLimitPolicy,
+        RATE_LIMIT_KICK_REASON
+    }
+};
+use bevy_replicon::{core::ClientId, prelude::*};
+use serde::{Deserialize, Serialize};
+
+const SETTLE_FRAMES: usize = 50;
+
+#[derive(Event, Serialize, Deserialize, Clone, Debug)]
+struct Flood(u32);
+
+fn connect(network: &LoopbackNetwork, limit: RateLimit) -> (App, [App; 1]) {
+    let mut server = common::server_app(network);
+    server.add_client_event::<Flood>(ChannelKind::Ordered)
+    .rate_limit_client_event::<Flood>(limit);
+    let mut clients = [common::client_app(network, 1)];
+    clients[0].add_client_event::<Flood>(ChannelKind::Ordered);
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    (server, clients)
+}
+
+fn flood(client: &mut App, count: u32) {
+    for i in 0..count {
+        client.world.send_event(Flood(i));
+    }
+}
+
+// events are kept for two frames only
+fn accepted(server: &mut App, clients: &mut [App], frames: usize) -> usize {
+    let mut reader = ManualEventReader::<AcceptedFromClient<Flood>>::default();
+    let mut accepted = 0;
+    for _ in 0..frames {
+        common::update(server, clients, 1);
+        let events = server.world.resource::<Events<AcceptedFromClient<Flood>>>();
+        accepted += reader.read(events).count();
+    }
+    accepted
+}
+
+fn state(client: &App) -> ClientConnectionState {
+    *client.world.resource::<State<ClientConnectionState>>().get()
+}
+
+#[test]
+fn events_over_the_limit_are_dropped() {
+    let network = LoopbackNetwork::default();
+    let (mut server, mut clients) = connect(&network, RateLimit{
+        capacity: 5,
+        refill_per_second: 10.0,
+        policy: RateLimitPolicy::Drop
+    });
+
+    flood(&mut clients[0], 20);
+    assert_eq!(accepted(&mut server, &mut clients, 5), 5);
+
+    // half a second refills 5 tokens
+    common::update(&mut server, &mut clients, 50);
+    flood(&mut clients[0], 20);
+    assert_eq!(accepted(&mut server, &mut clients, 5), 5);
+    assert_eq!(state(&clients[0]), ClientConnectionState::Connected);
+
+    let limiter = server.world.resource::<ClientEventRateLimiter<Flood>>();
+    let counters = limiter.client_counters(&ClientId::new(1))
+    .expect("client should be counted");
+    assert_eq!(counters.accepted, 10);
+    assert_eq!(counters.dropped, 30);
+    assert_eq!(counters.kicked, 0);
+    assert_eq!(limiter.counters().dropped, 30);
+}
+
+#[test]
+fn client_over_the_limit_is_kicked() {
+    let network = LoopbackNetwork::default();
+    let (mut server, mut clients) = connect(&network, RateLimit{
+        capacity: 5,
+        refill_per_second: 1.0,
+        policy: RateLimitPolicy::Kick
+    });
+
+    let mut reader = ManualEventReader::<ClientDisconnected>::default();
+    let mut disconnections = vec![];
+    flood(&mut clients[0], 20);
+    for _ in 0..SETTLE_FRAMES {
+        common::update(&mut server, &mut clients, 1);
+        let events = clients[0].world.resource::<Events<ClientDisconnected>>();
+        disconnections.extend(reader.read(events).cloned());
+    }
+    assert_eq!(state(&clients[0]), ClientConnectionState::Disconnected);
+    assert_eq!(disconnections.len(), 1);
+    assert_eq!(disconnections[0].reason, DisconnectReason::Server(RATE_LIMIT_KICK_REASON.to_string()));
+
+    // counters of the kicked client outlive its connection
+    let limiter = server.world.resource::<ClientEventRateLimiter<Flood>>();
+    let counters = limiter.client_counters(&ClientId::new(1))
+    .expect("kicked client should still be counted");
+    assert_eq!(counters.accepted, 5);
+    assert_eq!(counters.kicked, 1);
+    assert_eq!(limiter.counters().kicked, 1);
+}
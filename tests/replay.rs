@@ -0,0 +1,91 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+use bevy::prelude::*;
+use bevy_net_dev::{
+    dev::{
+        game::ActionEvent,
+        match_state::{MatchConfig, MatchPhase, MatchState},
+        replay::Replayer
+    },
+    netstack::{
+        conditioner::{LinkConditionerConfig, LinkConditions},
+        loopback::LoopbackNetwork,
+        recording::{replay_files, RecordingConfig, ReplayReader, SessionRecorder}
+    }
+};
+
+fn recording_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir()
+    .join(format!("bevy_net_dev-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    directory
+}
+
+fn match_phase(server: &mut App) -> Option<MatchPhase> {
+    let mut query = server.world.query::<&MatchState>();
+    query.get_single(&server.world).ok().map(|s| s.phase)
+}
+
+#[test]
+fn recorded_match_replays_without_divergence() {
+    let directory = recording_directory("match");
+    let network = LoopbackNetwork::with_seed(0);
+    // client keeps sending for a while after the match ends,
+    // those inputs are acked but not simulated
+    let link = LinkConditionerConfig{
+        latency_ms: 50.0,
+        ..default()
+    };
+    network.link_conditions().set(LinkConditions{
+        upstream: link,
+        downstream: link
+    });
+
+    let mut server = common::game_server_app(&network);
+    server.insert_resource(RecordingConfig{
+        directory: directory.clone(),
+        max_file_bytes: u64::MAX,
+        max_files: 1,
+        start_on_startup: true
+    })
+    .insert_resource(MatchConfig{
+        min_players: 1,
+        max_players: 0,
+        countdown_seconds: 0.3,
+        match_seconds: 0.5,
+        post_match_seconds: 0.3
+    });
+    let mut clients = [common::game_client_app(&network, 1)];
+
+    let mut phases = vec![];
+    for _ in 0..200 {
+        clients[0].world.send_event(ActionEvent{
+            movement_vec: Vec2::X,
+            is_fire: false
+        });
+        common::update(&mut server, &mut clients, 1);
+        if let Some(phase) = match_phase(&mut server) {
+            if phases.last() != Some(&phase) {
+                phases.push(phase);
+            }
+        }
+    }
+    assert!(phases.contains(&MatchPhase::InProgress), "phases: {phases:?}");
+    assert!(phases.contains(&MatchPhase::PostMatch), "phases: {phases:?}");
+
+    server.world.resource_mut::<SessionRecorder>().stop()
+    .expect("recording should be written");
+    let files = replay_files(&directory).expect("replay files should be listed");
+    assert_eq!(files.len(), 1);
+    let header = ReplayReader::open(&files[0])
+    .expect("replay should be readable")
+    .header()
+    .clone();
+
+    let report = Replayer::new(&header).run(&files)
+    .expect("replay should run");
+    assert!(report.frames > 0);
+    assert!(report.divergence.is_none(), "divergence: {:?}", report.divergence);
+    let _ = fs::remove_dir_all(&directory);
+}
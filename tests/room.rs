@@ -0,0 +1,65 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::netstack::{
+    components::NetworkPlayer,
+    loopback::LoopbackNetwork,
+    room::{InRoom, RoomJoinRequest, Rooms, DEFAULT_ROOM}
+};
+use bevy_replicon::core::ClientId;
+
+#[test]
+fn rooms_are_isolated() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [
+        common::client_app(&network, 1),
+        common::client_app(&network, 2),
+        common::client_app(&network, 3)
+    ];
+    // startup creates default room only
+    server.update();
+    let room = server.world.resource_mut::<Rooms>().create("other".to_string(), 0);
+    common::request_room(&mut clients[2], room);
+
+    common::update(&mut server, &mut clients, 20);
+    let rooms = server.world.resource::<Rooms>();
+    assert_eq!(rooms.client_room(&ClientId::new(1)), Some(DEFAULT_ROOM));
+    assert_eq!(rooms.client_room(&ClientId::new(3)), Some(room));
+
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1, 2]);
+    assert_eq!(common::visible_players(&mut clients[1]), vec![1, 2]);
+    assert_eq!(common::visible_players(&mut clients[2]), vec![3]);
+}
+
+#[test]
+fn clients_can_move_and_rooms_can_be_destroyed() {
+    let network = LoopbackNetwork::default();
+    let mut server = common::server_app(&network);
+    let mut clients = [
+        common::client_app(&network, 1),
+        common::client_app(&network, 2)
+    ];
+    server.update();
+    let room = server.world.resource_mut::<Rooms>().create("other".to_string(), 1);
+
+    common::update(&mut server, &mut clients, 20);
+    clients[1].world.send_event(RoomJoinRequest{ room });
+    common::update(&mut server, &mut clients, 20);
+    assert_eq!(server.world.resource::<Rooms>().client_room(&ClientId::new(2)), Some(room));
+    assert_eq!(common::visible_players(&mut clients[0]), vec![1]);
+    assert_eq!(common::visible_players(&mut clients[1]), vec![2]);
+
+    // room is full
+    clients[0].world.send_event(RoomJoinRequest{ room });
+    common::update(&mut server, &mut clients, 20);
+    assert_eq!(server.world.resource::<Rooms>().client_room(&ClientId::new(1)), Some(DEFAULT_ROOM));
+
+    server.world.resource_mut::<Rooms>().destroy(room).unwrap();
+    common::update(&mut server, &mut clients, 20);
+    assert!(server.world.resource::<Rooms>().get(&room).is_none());
+    assert_eq!(common::visible_players(&mut clients[1]), vec![1, 2]);
+
+    let mut query = clients[1].world.query::<(&NetworkPlayer, &InRoom)>();
+    assert!(query.iter(&clients[1].world).all(|(_, r)| r.0 == DEFAULT_ROOM));
+}
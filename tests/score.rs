@@ -0,0 +1,118 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_net_dev::{
+    dev::{
+        game::ActionEvent,
+        score::{Health, ScoreConfig, Scoreboard},
+        team::{Team, TeamConfig}
+    },
+    netstack::{
+        components::{NetworkPlayer, NetworkTranslation2D},
+        loopback::LoopbackNetwork,
+        room::{InRoom, Rooms, DEFAULT_ROOM}
+    }
+};
+use bevy_replicon::core::ClientId;
+use bevy_replicon_snap::prelude::*;
+
+const SETTLE_FRAMES: usize = 100;
+
+fn fire(server: &mut App, clients: &mut [App], shooter: usize, target: u64) {
+    let target = common::server_translation(server, target);
+    common::aim(server, shooter as u64 + 1, target);
+    common::update(server, clients, SETTLE_FRAMES);
+    clients[shooter].world.send_event(ActionEvent{
+        movement_vec: Vec2::ZERO,
+        is_fire: true
+    });
+    common::update(server, clients, SETTLE_FRAMES);
+}
+
+fn scoreboard(app: &mut App) -> Scoreboard {
+    let mut query = app.world.query::<&Scoreboard>();
+    query.get_single(&app.world)
+    .expect("scoreboard should be replicated")
+    .clone()
+}
+
+#[test]
+fn kills_deaths_and_assists_are_replicated() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    server.world.resource_mut::<ScoreConfig>().hit_damage = 50;
+
+    let mut clients = vec![];
+    for (i, team) in [Team::Red, Team::Blue, Team::Red].into_iter().enumerate() {
+        let mut client = common::game_client_app(&network, i as u64 + 1);
+        common::request_team(&mut client, Some(team));
+        clients.push(client);
+        common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    }
+    assert_eq!(scoreboard(&mut clients[1]).iter().count(), 3);
+
+    fire(&mut server, &mut clients, 2, 2);
+    let mut query = clients[0].world.query::<(&NetworkPlayer, &Health)>();
+    let victim_health = query.iter(&clients[0].world)
+    .find(|(p, _)| p.client_id().get() == 2)
+    .map(|(_, h)| *h);
+    assert_eq!(victim_health, Some(Health(50)));
+
+    fire(&mut server, &mut clients, 0, 2);
+    let scoreboard = scoreboard(&mut clients[1]);
+    let entry = |id: u64| scoreboard.get(&ClientId::new(id))
+    .cloned()
+    .expect("player should be on scoreboard");
+    assert_eq!((entry(1).kills, entry(1).deaths, entry(1).assists), (1, 0, 0));
+    assert_eq!((entry(2).kills, entry(2).deaths, entry(2).assists), (0, 1, 0));
+    assert_eq!((entry(3).kills, entry(3).deaths, entry(3).assists), (0, 0, 1));
+    assert_eq!(entry(2).team, Some(Team::Blue));
+    // killer is listed first
+    assert_eq!(scoreboard.iter().next().map(|e| e.client_id), Some(ClientId::new(1)));
+
+    // victim respawned with full health
+    let mut query = server.world.query::<(&NetworkPlayer, &Health)>();
+    let victim_health = query.iter(&server.world)
+    .find(|(p, _)| p.client_id().get() == 2)
+    .map(|(_, h)| *h);
+    assert_eq!(victim_health, Some(Health(100)));
+
+    // on a free point of its own side, and fire looks up the respawn point from then on
+    let respawned = common::server_translation(&server, 2);
+    let occupied = [common::server_translation(&server, 1), common::server_translation(&server, 3)];
+    let team_config = server.world.resource::<TeamConfig>();
+    assert_eq!(respawned, team_config.free_spawn_point(Team::Blue, &occupied));
+    let victim = common::player_entity(&server, 2);
+    let buffer = server.world.get::<ComponentSnapshotBuffer<NetworkTranslation2D>>(victim)
+    .expect("player should have snapshots");
+    let latest = buffer.iter().last().expect("snapshots should not be empty");
+    assert_eq!(latest.component().0, respawned);
+}
+
+#[test]
+fn scoreboard_lists_players_of_own_room() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = [
+        common::game_client_app(&network, 1),
+        common::game_client_app(&network, 2),
+        common::game_client_app(&network, 3)
+    ];
+    server.update();
+    let room = server.world.resource_mut::<Rooms>().create("other".to_string(), 0);
+    common::request_room(&mut clients[2], room);
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let ids = |scoreboard: Scoreboard| scoreboard.iter()
+    .map(|e| e.client_id.get())
+    .collect::<Vec<_>>();
+    let mut default_room = ids(scoreboard(&mut clients[0]));
+    default_room.sort();
+    assert_eq!(default_room, vec![1, 2]);
+    assert_eq!(ids(scoreboard(&mut clients[2])), vec![3]);
+
+    let mut query = server.world.query_filtered::<&InRoom, With<Scoreboard>>();
+    let mut rooms = query.iter(&server.world).map(|r| r.0.0).collect::<Vec<_>>();
+    rooms.sort();
+    assert_eq!(rooms, vec![DEFAULT_ROOM.0, room.0]);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_net_dev::{
+    dev::{
+        config::DEV_FIRE_HIT_RADIUS,
+        game::{ActionEvent, HitEvent, PlayerPresentation},
+        team::{Team, TeamConfig}
+    },
+    netstack::{
+        components::NetworkPlayer,
+        loopback::LoopbackNetwork
+    }
+};
+use bevy_replicon_renet::renet::RenetClient;
+
+const SETTLE_FRAMES: usize = 100;
+
+// connects one by one so that assignment order is known
+fn connect(
+    network: &LoopbackNetwork,
+    server: &mut App,
+    clients: &mut Vec<App>,
+    requests: &[Option<Team>]
+) {
+    for (i, team) in requests.iter().enumerate() {
+        let mut client = common::game_client_app(network, i as u64 + 1);
+        common::request_team(&mut client, *team);
+        clients.push(client);
+        common::update(server, clients, SETTLE_FRAMES);
+    }
+}
+
+fn aim_and_fire(server: &mut App, clients: &mut [App], shooter: usize, target: Vec2) -> Vec<Entity> {
+    common::aim(server, shooter as u64 + 1, target);
+    common::update(server, clients, SETTLE_FRAMES);
+    fire(server, clients, shooter)
+}
+
+fn fire(server: &mut App, clients: &mut [App], shooter: usize) -> Vec<Entity> {
+    let mut reader = ManualEventReader::<HitEvent>::default();
+    reader.clear(server.world.resource::<Events<HitEvent>>());
+    clients[shooter].world.send_event(ActionEvent{
+        movement_vec: Vec2::ZERO,
+        is_fire: true
+    });
+
+    let mut targets = vec![];
+    for _ in 0..SETTLE_FRAMES {
+        common::update(server, clients, 1);
+        let events = server.world.resource::<Events<HitEvent>>();
+        targets.extend(reader.read(events).map(|h| h.target));
+    }
+    targets
+}
+
+#[test]
+fn teams_are_assigned_and_balanced() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = vec![];
+    connect(&network, &mut server, &mut clients, &[
+        Some(Team::Blue),
+        // would unbalance teams
+        Some(Team::Blue),
+        None,
+        None
+    ]);
+
+    let expected = [Team::Blue, Team::Red, Team::Red, Team::Blue];
+    for (i, team) in expected.iter().enumerate() {
+        let e = common::player_entity(&server, i as u64 + 1);
+        assert_eq!(server.world.get::<Team>(e), Some(team));
+        let translation = common::server_translation(&server, i as u64 + 1);
+        // teams spawn on their own side
+        let side = if *team == Team::Red { -1.0 } else { 1.0 };
+        assert_eq!(translation.x.signum(), side);
+    }
+
+    let mut query = clients[0].world.query::<(&NetworkPlayer, &Team, &PlayerPresentation)>();
+    let mut replicated = query.iter(&clients[0].world)
+    .map(|(p, t, presentation)| {
+        assert_eq!(presentation.color, t.color());
+        (p.client_id().get(), *t)
+    })
+    .collect::<Vec<_>>();
+    replicated.sort_by_key(|(id, _)| *id);
+    assert_eq!(replicated, vec![
+        (1, Team::Blue), (2, Team::Red), (3, Team::Red), (4, Team::Blue)
+    ]);
+}
+
+#[test]
+fn friendly_fire_follows_config() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = vec![];
+    connect(&network, &mut server, &mut clients, &[
+        Some(Team::Red),
+        Some(Team::Blue),
+        Some(Team::Red)
+    ]);
+    let enemy = common::player_entity(&server, 2);
+    let teammate = common::player_entity(&server, 3);
+
+    let (enemy_at, teammate_at) = (common::server_translation(&server, 2), common::server_translation(&server, 3));
+
+    let hits = aim_and_fire(&mut server, &mut clients, 0, enemy_at);
+    assert_eq!(hits, vec![enemy]);
+    let hits = aim_and_fire(&mut server, &mut clients, 0, teammate_at);
+    assert!(hits.is_empty(), "hits: {hits:?}");
+
+    server.world.resource_mut::<TeamConfig>().friendly_fire = true;
+    let hits = fire(&mut server, &mut clients, 0);
+    assert_eq!(hits, vec![teammate]);
+}
+
+#[test]
+fn fire_hits_only_what_is_aimed_at() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = vec![];
+    connect(&network, &mut server, &mut clients, &[
+        Some(Team::Red),
+        Some(Team::Blue),
+        Some(Team::Blue)
+    ]);
+    let enemy = common::player_entity(&server, 2);
+    // next to the aimed one, out of the line of fire
+    let other_enemy = common::player_entity(&server, 3);
+    let (enemy_at, other_enemy_at) = (common::server_translation(&server, 2), common::server_translation(&server, 3));
+    assert!(enemy_at.distance(other_enemy_at) > DEV_FIRE_HIT_RADIUS);
+
+    let hits = aim_and_fire(&mut server, &mut clients, 0, enemy_at);
+    assert_eq!(hits, vec![enemy]);
+    let hits = aim_and_fire(&mut server, &mut clients, 0, other_enemy_at);
+    assert_eq!(hits, vec![other_enemy]);
+
+    // turned away
+    let away = common::server_translation(&server, 1) * 2.0;
+    let hits = aim_and_fire(&mut server, &mut clients, 0, away);
+    assert!(hits.is_empty(), "hits: {hits:?}");
+}
+
+#[test]
+fn spawn_slots_of_players_who_left_are_reused() {
+    let network = LoopbackNetwork::with_seed(0);
+    let mut server = common::game_server_app(&network);
+    let mut clients = vec![];
+    connect(&network, &mut server, &mut clients, &[
+        Some(Team::Red),
+        Some(Team::Blue),
+        Some(Team::Red)
+    ]);
+    let first = common::server_translation(&server, 1);
+    let teammate = common::server_translation(&server, 3);
+
+    clients[0].world.resource_mut::<RenetClient>().disconnect();
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+    clients.remove(0);
+    let mut client = common::game_client_app(&network, 4);
+    common::request_team(&mut client, Some(Team::Red));
+    clients.push(client);
+    common::update(&mut server, &mut clients, SETTLE_FRAMES);
+
+    let joined = common::server_translation(&server, 4);
+    assert_eq!(joined, first);
+    assert_ne!(joined, teammate);
+}